@@ -0,0 +1,151 @@
+use rust_interpreter::{calc_wormholes, parse_hand_code, run_hand_ast, translate, Instruction};
+use rust_interpreter::{Interpreter, InterpreterConfig};
+use std::collections::BTreeMap;
+use std::io;
+use std::time::{Duration, Instant};
+
+// The interpreter as it was before jumps were compiled in: one instruction at a
+// time, with a map lookup on every loop boundary.
+fn run_with_wormholes(instructions: &[Instruction], wormholes: &BTreeMap<usize, usize>) -> Vec<u8> {
+    use Instruction::*;
+    let mut output = Vec::new();
+    let mut buffer = vec![0u8];
+    let mut cursor = 0usize;
+    let mut flow_offset = 0usize;
+
+    while let Some(ins) = instructions.get(flow_offset) {
+        match ins {
+            Next => {
+                cursor += 1;
+                if buffer.get(cursor).is_none() {
+                    buffer.push(0u8);
+                }
+            }
+            Previous => cursor -= 1,
+            Increment => buffer[cursor] = buffer[cursor].wrapping_add(1),
+            Decrease => buffer[cursor] = buffer[cursor].wrapping_sub(1),
+            LoopStart if buffer[cursor] == 0 => flow_offset = wormholes[&flow_offset],
+            LoopEnd if buffer[cursor] != 0 => flow_offset = wormholes[&flow_offset],
+            Print => output.push(buffer[cursor]),
+            _ => (),
+        }
+        flow_offset += 1;
+    }
+
+    output
+}
+
+fn time(iterations: u32, mut f: impl FnMut()) -> Duration {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    start.elapsed() / iterations
+}
+
+fn bench(name: &str, code: &str, iterations: u32) {
+    let (_, instructions) = parse_hand_code(code).unwrap();
+    let wormholes = calc_wormholes(&instructions).unwrap();
+
+    let mut expected = Vec::new();
+    run_hand_ast(io::empty(), &mut expected, &instructions).unwrap();
+    assert_eq!(run_with_wormholes(&instructions, &wormholes), expected);
+
+    let baseline = time(iterations, || {
+        run_with_wormholes(&instructions, &wormholes);
+    });
+    let compiled = time(iterations, || {
+        run_hand_ast(io::empty(), io::sink(), &instructions).unwrap();
+    });
+    println!(
+        "{:<12} wormholes {:>12?}  compiled {:>12?}  ({:.1}x)",
+        name,
+        baseline,
+        compiled,
+        baseline.as_secs_f64() / compiled.as_secs_f64()
+    );
+}
+
+// Interpreter::run against calling step until it returns None, which is how
+// run used to work: an Option and a StepResult for every op.
+fn bench_run_loop(name: &str, code: &str, iterations: u32) {
+    let (_, instructions) = parse_hand_code(code).unwrap();
+    let bytecode = translate(&instructions).unwrap();
+    let config = InterpreterConfig::default();
+    let interpreter = || Interpreter::with_config(&config, &bytecode, io::empty(), io::sink());
+
+    let stepped = time(iterations, || {
+        let mut interpreter = interpreter();
+        while interpreter.step().unwrap().is_some() {}
+    });
+    let run = time(iterations, || {
+        interpreter().run().unwrap();
+    });
+    println!(
+        "{:<12} stepped   {:>12?}  run      {:>12?}  ({:.1}x)",
+        name,
+        stepped,
+        run,
+        stepped.as_secs_f64() / run.as_secs_f64()
+    );
+}
+
+// Three nested countdown loops that shuffle values between cells, which keeps
+// the interpreter busy with jumps for a few million steps.
+fn nested_loops() -> String {
+    let counter = "👆".repeat(100);
+    format!(
+        "{c}🤜👉{c}🤜👉{c}🤜👇👉👆👈🤛👉🤜👇👈👆👉🤛👈👈👇🤛👈👇🤛👉👉👉👊",
+        c = counter
+    )
+}
+
+// A program of about n instructions with comments, runs of moves and loops
+// nested n / 6 deep, like a long generated program would be.
+fn large_program(n: usize) -> String {
+    let depth = n / 6;
+    format!(
+        "👆{}👉👆👈👇 # the middle\n{}👊",
+        "🤜👆👉 # open\n".repeat(depth),
+        "👈👇🤛 # close\n".repeat(depth)
+    )
+}
+
+// Parsing, matching loops and compiling should all take time linear in the
+// length of the program, so the time per instruction should stay about the
+// same as the program doubles.
+fn bench_scaling(iterations: u32) {
+    for n in [20_000, 40_000, 80_000, 160_000] {
+        let code = large_program(n);
+        let instructions = rust_interpreter::parse_program(&code).unwrap();
+        let per_instruction = |f: &mut dyn FnMut()| time(iterations, f) / instructions.len() as u32;
+        let parse = per_instruction(&mut || {
+            rust_interpreter::parse_program(&code).unwrap();
+        });
+        let wormholes = per_instruction(&mut || {
+            calc_wormholes(&instructions).unwrap();
+        });
+        let compile = per_instruction(&mut || {
+            rust_interpreter::compile(&instructions).unwrap();
+        });
+        println!(
+            "{:>7} ins  parse {:>8?}/ins  wormholes {:>8?}/ins  compile {:>8?}/ins",
+            instructions.len(),
+            parse,
+            wormholes,
+            compile
+        );
+    }
+}
+
+fn main() {
+    bench("hello", include_str!("../../test1.hand").trim(), 10_000);
+    bench(
+        "hello world",
+        include_str!("../../test2.hand").trim(),
+        10_000,
+    );
+    bench("nested", &nested_loops(), 3);
+    bench_run_loop("nested", &nested_loops(), 3);
+    bench_scaling(10);
+}
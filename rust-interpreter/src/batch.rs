@@ -0,0 +1,78 @@
+use crate::{HandError, InterpreterConfig, Program};
+use alloc::vec::Vec;
+
+#[derive(Debug)]
+pub enum Outcome {
+    Passed,
+    Failed { output: Vec<u8> }, // the program ran but printed this instead
+    Error(HandError),           // parsing or running the program failed
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        matches!(self, Outcome::Passed)
+    }
+}
+
+// Runs every (source, input, expected output) case with the same config and
+// returns an outcome for each, in order. A case that fails doesn't stop the
+// ones after it.
+pub fn run_batch<S, I, E>(
+    config: &InterpreterConfig,
+    cases: impl IntoIterator<Item = (S, I, E)>,
+) -> Vec<Outcome>
+where
+    S: AsRef<str>,
+    I: AsRef<[u8]>,
+    E: AsRef<[u8]>,
+{
+    cases
+        .into_iter()
+        .map(|(source, input, expected)| {
+            let mut output = Vec::new();
+            let result = Program::compile(source.as_ref())
+                .and_then(|program| program.run_with_config(config, input.as_ref(), &mut output));
+            match result {
+                Err(e) => Outcome::Error(e),
+                Ok(()) if output == expected.as_ref() => Outcome::Passed,
+                Ok(()) => Outcome::Failed { output },
+            }
+        })
+        .collect()
+}
+
+#[test]
+pub fn test_batch() {
+    let hello = include_str!("../../test1.hand").trim();
+    let hello_world = include_str!("../../test2.hand").trim();
+    let config = InterpreterConfig {
+        max_steps: Some(10_000),
+        ..InterpreterConfig::default()
+    };
+
+    let outcomes = run_batch(
+        &config,
+        [
+            (hello, "", "Hello"),
+            (hello_world, "", "Hello World!\n"),
+            (hello, "", "Goodbye"),
+            ("👆🤜🤛", "", ""),
+            ("🤜", "", ""),
+            ("✋👊✋👊", "hi", "hi"),
+        ],
+    );
+
+    assert_eq!(outcomes.len(), 6);
+    assert!(outcomes[0].passed());
+    assert!(outcomes[1].passed());
+    assert!(matches!(&outcomes[2], Outcome::Failed { output } if output == b"Hello"));
+    assert!(matches!(
+        outcomes[3],
+        Outcome::Error(HandError::StepLimitExceeded { limit: 10_000, .. })
+    ));
+    assert!(matches!(
+        outcomes[4],
+        Outcome::Error(HandError::UnmatchedLoopStart { offset: 0 })
+    ));
+    assert!(outcomes[5].passed());
+}
@@ -0,0 +1,414 @@
+use crate::{analyze_loop_body, HandError, Instruction, LoopEffect};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::Write;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Op {
+    Add(i16),             // add to the memory cell at the current position, wrapping around
+    Move(isize),          // move the memory pointer, negative values move it to the left
+    JumpIfZero(usize),    // 🤜, continues after the op at the given index when the cell is 0
+    JumpIfNonZero(usize), // 🤛, continues after the op at the given index when the cell is not 0
+    Print,                // same as 👊
+    Input,                // same as ✋
+    Clear,                // 🤜👇🤛, sets the memory cell at the current position to 0
+    MultiplyLoop(usize),  // 🤜 of a loop that only adds and moves back, see is_multiply_loop
+    SeekZero(isize),      // 🤜👉🤛 or 🤜👈🤛, moves by the given step until the cell is 0
+    PrintRepeat(usize),   // a run of 👊, the cell is written that many times in one go
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Bytecode {
+    pub ops: Vec<Op>,
+    pub offsets: Vec<usize>, // offset of the first instruction each op was compiled from
+}
+
+// Signed amounts always show their sign, so `Add +8` and `Move -1`.
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Op::Add(n) => write!(f, "Add {:+}", n),
+            Op::Move(n) => write!(f, "Move {:+}", n),
+            Op::JumpIfZero(target) => write!(f, "JumpIfZero {}", target),
+            Op::JumpIfNonZero(target) => write!(f, "JumpIfNonZero {}", target),
+            Op::Print => f.write_str("Print"),
+            Op::Input => f.write_str("Input"),
+            Op::Clear => f.write_str("Clear"),
+            Op::MultiplyLoop(end) => write!(f, "MultiplyLoop {}", end),
+            Op::SeekZero(step) => write!(f, "SeekZero {:+}", step),
+            Op::PrintRepeat(count) => write!(f, "PrintRepeat {}", count),
+        }
+    }
+}
+
+impl Bytecode {
+    // One op per line after its index, with the offset of the instruction it
+    // was compiled from at the end.
+    pub fn disassemble(&self) -> String {
+        let mut text = String::new();
+        for (index, (op, offset)) in self.ops.iter().zip(&self.offsets).enumerate() {
+            let _ = writeln!(text, "{:04} {:<20} # {}", index, op.to_string(), offset);
+        }
+        text
+    }
+}
+
+#[derive(Default)]
+struct Builder<'a> {
+    bytecode: Bytecode,
+    starts: Vec<usize>,
+    fuse_loops: Option<&'a [Instruction]>, // the program, when loops may become single ops
+}
+
+impl Builder<'_> {
+    fn push(&mut self, op: Op, offset: usize) {
+        self.bytecode.ops.push(op);
+        self.bytecode.offsets.push(offset);
+    }
+
+    fn push_instruction(&mut self, ins: Instruction, offset: usize) -> Result<(), HandError> {
+        match ins {
+            Instruction::Next => self.push(Op::Move(1), offset),
+            Instruction::Previous => self.push(Op::Move(-1), offset),
+            Instruction::Increment => self.push(Op::Add(1), offset),
+            Instruction::Decrease => self.push(Op::Add(-1), offset),
+            Instruction::LoopStart => {
+                self.starts.push(self.bytecode.ops.len());
+                self.push(Op::JumpIfZero(0), offset);
+            }
+            Instruction::LoopEnd => {
+                let start = self
+                    .starts
+                    .pop()
+                    .ok_or(HandError::UnmatchedLoopEnd { offset })?;
+                let end = self.bytecode.ops.len();
+                let body = self
+                    .fuse_loops
+                    .map(|instructions| &instructions[self.bytecode.offsets[start] + 1..offset]);
+                self.bytecode.ops[start] = if body.is_some_and(is_multiply_loop) {
+                    Op::MultiplyLoop(end)
+                } else {
+                    Op::JumpIfZero(end)
+                };
+                self.push(Op::JumpIfNonZero(start), offset);
+            }
+            Instruction::Print => self.push(Op::Print, offset),
+            Instruction::Input => self.push(Op::Input, offset),
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Bytecode, HandError> {
+        if let Some(&start) = self.starts.last() {
+            let offset = self.bytecode.offsets[start];
+            return Err(HandError::UnmatchedLoopStart { offset });
+        }
+        Ok(self.bytecode)
+    }
+}
+
+const CLEAR_LOOP: [Instruction; 3] = [
+    Instruction::LoopStart,
+    Instruction::Decrease,
+    Instruction::LoopEnd,
+];
+
+// A loop body that only adds and moves, ends up where it started and takes
+// exactly one from the cell it started on. Running it n times adds n times each
+// other amount to its cell, so the interpreter can do all iterations at once.
+fn is_multiply_loop(body: &[Instruction]) -> bool {
+    let effect = LoopEffect {
+        cell_delta: -1,
+        pointer_delta: 0,
+    };
+    analyze_loop_body(body) == Some(effect)
+}
+
+// Folds runs of the same instruction into a single counted op. Only identical
+// instructions are folded, so boundary checks fail at exactly the same point as
+// when running one instruction at a time.
+pub fn compile(instructions: &[Instruction]) -> Result<Bytecode, HandError> {
+    let mut builder = Builder {
+        fuse_loops: Some(instructions),
+        ..Builder::default()
+    };
+    let mut offset = 0usize;
+
+    while let Some(&ins) = instructions.get(offset) {
+        if instructions[offset..].starts_with(&CLEAR_LOOP) {
+            builder.push(Op::Clear, offset);
+            offset += CLEAR_LOOP.len();
+            continue;
+        }
+        if let Some((step, len)) = seek_loop(&instructions[offset..]) {
+            builder.push(Op::SeekZero(step), offset);
+            offset += len;
+            continue;
+        }
+        // only counted for foldable instructions, long runs of 🤜 would make this quadratic
+        let run = || {
+            instructions[offset..]
+                .iter()
+                .take_while(|&&other| other == ins)
+                .count()
+        };
+        match ins {
+            Instruction::Increment | Instruction::Decrease => {
+                let run = run();
+                let sign = if ins == Instruction::Increment { 1 } else { -1 };
+                let mut start = offset;
+                for chunk in run_chunks(run, i16::MAX as usize) {
+                    builder.push(Op::Add(sign * chunk as i16), start);
+                    start += chunk;
+                }
+                offset += run;
+            }
+            Instruction::Next | Instruction::Previous => {
+                let run = run();
+                let sign = if ins == Instruction::Next { 1 } else { -1 };
+                builder.push(Op::Move(sign * run as isize), offset);
+                offset += run;
+            }
+            // nothing changes the cell in between, so every 👊 writes the same
+            Instruction::Print => {
+                let run = run();
+                let op = if run > 1 {
+                    Op::PrintRepeat(run)
+                } else {
+                    Op::Print
+                };
+                builder.push(op, offset);
+                offset += run;
+            }
+            _ => {
+                builder.push_instruction(ins, offset)?;
+                offset += 1;
+            }
+        }
+    }
+
+    builder.finish()
+}
+
+// One op per instruction, useful to compare against the folded form.
+pub fn translate(instructions: &[Instruction]) -> Result<Bytecode, HandError> {
+    let mut builder = Builder::default();
+    for (offset, &ins) in instructions.iter().enumerate() {
+        builder.push_instruction(ins, offset)?;
+    }
+    builder.finish()
+}
+
+// One op per instruction with every jump target left at 0, for an interpreter
+// that matches loops as it goes. Unmatched loops are not an error here.
+pub(crate) fn translate_unresolved(instructions: &[Instruction]) -> Bytecode {
+    Bytecode {
+        ops: instructions.iter().map(|&ins| unresolved_op(ins)).collect(),
+        offsets: (0..instructions.len()).collect(),
+    }
+}
+
+pub(crate) fn unresolved_op(ins: Instruction) -> Op {
+    match ins {
+        Instruction::Next => Op::Move(1),
+        Instruction::Previous => Op::Move(-1),
+        Instruction::Increment => Op::Add(1),
+        Instruction::Decrease => Op::Add(-1),
+        Instruction::LoopStart => Op::JumpIfZero(0),
+        Instruction::LoopEnd => Op::JumpIfNonZero(0),
+        Instruction::Print => Op::Print,
+        Instruction::Input => Op::Input,
+    }
+}
+
+// A loop made of a single run of 👉 or 👈, returns the step and the length of the loop.
+fn seek_loop(instructions: &[Instruction]) -> Option<(isize, usize)> {
+    let (&first, rest) = instructions.split_first()?;
+    let &direction = rest.first()?;
+    let sign = match direction {
+        Instruction::Next => 1,
+        Instruction::Previous => -1,
+        _ => return None,
+    };
+    let run = rest.iter().take_while(|&&ins| ins == direction).count();
+    let closed = rest.get(run) == Some(&Instruction::LoopEnd);
+    (first == Instruction::LoopStart && closed).then_some((sign * run as isize, run + 2))
+}
+
+fn run_chunks(mut run: usize, max: usize) -> impl Iterator<Item = usize> {
+    core::iter::from_fn(move || {
+        let chunk = run.min(max);
+        run -= chunk;
+        (chunk > 0).then_some(chunk)
+    })
+}
+
+#[test]
+pub fn test_compile_folds_runs() {
+    use Instruction::*;
+    let instructions = [
+        Increment, Increment, Increment, Next, Next, Decrease, Previous, LoopStart, LoopEnd, Print,
+    ];
+
+    let bytecode = compile(&instructions).unwrap();
+
+    assert_eq!(
+        bytecode.ops,
+        vec![
+            Op::Add(3),
+            Op::Move(2),
+            Op::Add(-1),
+            Op::Move(-1),
+            Op::JumpIfZero(5),
+            Op::JumpIfNonZero(4),
+            Op::Print,
+        ]
+    );
+    assert_eq!(bytecode.offsets, vec![0, 3, 5, 6, 7, 8, 9]);
+}
+
+#[test]
+pub fn test_compile_splits_long_runs() {
+    let instructions = vec![Instruction::Decrease; i16::MAX as usize + 2];
+
+    let bytecode = compile(&instructions).unwrap();
+
+    assert_eq!(bytecode.ops, vec![Op::Add(-i16::MAX), Op::Add(-2)]);
+    assert_eq!(bytecode.offsets, vec![0, i16::MAX as usize]);
+}
+
+#[test]
+pub fn test_compile_clear_loops() {
+    use Instruction::*;
+    let instructions = [
+        Increment, LoopStart, Decrease, LoopEnd, Next, LoopStart, Decrease, Next, LoopEnd,
+    ];
+
+    let bytecode = compile(&instructions).unwrap();
+
+    assert_eq!(
+        bytecode.ops,
+        vec![
+            Op::Add(1),
+            Op::Clear,
+            Op::Move(1),
+            Op::JumpIfZero(6),
+            Op::Add(-1),
+            Op::Move(1),
+            Op::JumpIfNonZero(3),
+        ]
+    );
+    assert_eq!(bytecode.offsets, vec![0, 1, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+pub fn test_compile_reports_unbalanced_loops() {
+    use Instruction::*;
+
+    let result = compile(&[Increment, LoopStart, Next, Next]);
+    assert!(matches!(
+        result,
+        Err(HandError::UnmatchedLoopStart { offset: 1 })
+    ));
+
+    let result = translate(&[Increment, LoopEnd]);
+    assert!(matches!(
+        result,
+        Err(HandError::UnmatchedLoopEnd { offset: 1 })
+    ));
+}
+
+#[test]
+pub fn test_compile_multiply_loops() {
+    let bytecode = |code| compile(&crate::parse_program(code).unwrap()).unwrap().ops;
+
+    // cell 1 += 2 * cell 0, cell 2 += cell 0
+    assert_eq!(
+        bytecode("🤜👇👉👆👆👉👆👈👈🤛"),
+        vec![
+            Op::MultiplyLoop(7),
+            Op::Add(-1),
+            Op::Move(1),
+            Op::Add(2),
+            Op::Move(1),
+            Op::Add(1),
+            Op::Move(-2),
+            Op::JumpIfNonZero(0),
+        ]
+    );
+    assert_eq!(bytecode("🤜👉👆👈👇🤛")[0], Op::MultiplyLoop(5));
+
+    // ends somewhere else, prints, takes two or reads
+    assert_eq!(bytecode("🤜👇👉👆🤛")[0], Op::JumpIfZero(4));
+    assert_eq!(bytecode("🤜👇👉👊👈🤛")[0], Op::JumpIfZero(5));
+    assert_eq!(bytecode("🤜👇👇👉👆👈🤛")[0], Op::JumpIfZero(5));
+    assert_eq!(bytecode("🤜👇👉✋👈🤛")[0], Op::JumpIfZero(5));
+    assert_eq!(bytecode("🤜👇👉🤜👇🤛👈🤛")[0], Op::JumpIfZero(5));
+
+    let translated = translate(&crate::parse_program("🤜👇👉👆👈🤛").unwrap()).unwrap();
+    assert_eq!(translated.ops[0], Op::JumpIfZero(5));
+}
+
+#[test]
+pub fn test_compile_seek_loops() {
+    let bytecode = |code| compile(&crate::parse_program(code).unwrap()).unwrap();
+
+    let seek = bytecode("👆🤜👉🤛👊🤜👈👈🤛");
+    assert_eq!(
+        seek.ops,
+        vec![Op::Add(1), Op::SeekZero(1), Op::Print, Op::SeekZero(-2)]
+    );
+    assert_eq!(seek.offsets, vec![0, 1, 4, 5]);
+
+    assert_eq!(bytecode("🤜👉👈🤛").ops[0], Op::JumpIfZero(3));
+    assert_eq!(bytecode("🤜👉👆🤛").ops[0], Op::JumpIfZero(3));
+}
+
+#[test]
+pub fn test_compile_print_runs() {
+    let bytecode = |code| compile(&crate::parse_program(code).unwrap()).unwrap();
+
+    let runs = bytecode("👆👊👊👊👉👊👆👊👊");
+    assert_eq!(
+        runs.ops,
+        vec![
+            Op::Add(1),
+            Op::PrintRepeat(3),
+            Op::Move(1),
+            Op::Print,
+            Op::Add(1),
+            Op::PrintRepeat(2),
+        ]
+    );
+    assert_eq!(runs.offsets, vec![0, 1, 4, 5, 6, 7]);
+    assert_eq!(Op::PrintRepeat(3).to_string(), "PrintRepeat 3");
+}
+
+#[test]
+pub fn test_disassemble() -> Result<(), HandError> {
+    let code = include_str!("../../test2.hand").trim();
+    let (_, instructions) = crate::parse_hand_code(code).unwrap();
+
+    let text = compile(&instructions)?.disassemble();
+
+    assert_eq!(crate::Program::compile(code)?.disassemble(), text);
+    let lines: Vec<_> = text.lines().collect();
+    assert_eq!(lines[0], "0000 Move +1              # 0");
+    assert_eq!(lines[1], "0001 Add +8               # 1");
+    assert_eq!(lines[2], "0002 MultiplyLoop 7       # 9");
+    assert!(text.contains(" Move -1 "));
+    assert!(text.contains(" Add +9 "));
+    assert!(text.contains(" JumpIfNonZero "));
+    assert_eq!(lines.len(), compile(&instructions)?.ops.len());
+    assert_eq!(
+        crate::translate(&instructions)?
+            .disassemble()
+            .lines()
+            .count(),
+        instructions.len()
+    );
+    assert_eq!(Op::SeekZero(-1).to_string(), "SeekZero -1");
+
+    Ok(())
+}
@@ -0,0 +1,394 @@
+use crate::Cell;
+use alloc::sync::Arc;
+use core::fmt;
+use core::time::Duration;
+
+#[derive(Clone, Debug)]
+pub struct InterpreterConfig {
+    pub initial_tape_size: usize, // cells allocated before the program starts (at least one)
+    pub max_tape_size: Option<usize>, // growing the tape past this many cells fails with TapeOverflow
+    pub boundary: BoundaryPolicy, // what happens when the pointer moves off either end of the tape
+    pub max_steps: Option<u64>, // running more instructions in one run fails with StepLimitExceeded
+    pub max_duration: Option<Duration>, // running longer than this in one run fails with Timeout, needs std
+    pub max_output_bytes: Option<u64>,  // more output than this fails with OutputLimitExceeded
+    pub output: OutputMode,             // how 👊 turns the current cell into output bytes
+    pub line_ending: LineEnding,        // what 👊 writes for a cell holding \n
+    pub strict_ascii: bool,             // 👊 of anything but ASCII text fails with NonAsciiOutput
+    pub nul: NulPolicy,                 // what 👊 does with a cell that would be written as NUL
+    pub eof: EofPolicy,                 // what ✋ stores once the input has run out
+    pub flush_every: Option<u64>,       // flush after this many 👊 (0 is 1) and when a run ends
+    pub cell_width: CellWidth,          // size of each memory cell
+    pub initial_cell_value: Cell,       // what every cell holds before the program changes it
+    pub overflow: OverflowMode,         // what 👆 and 👇 do past the limits of a cell
+    pub tape: TapeKind,                 // how cells are stored, ignored for the fixed size tapes
+    pub signed: bool, // cells are two's complement numbers for overflow limits and dumps
+    pub max_loop_depth: Option<usize>, // programs nesting loops deeper than this fail with LoopTooDeep
+    pub on_output: Option<OutputHook>, // called after every 👊 has written its output
+    pub on_overflow: Option<OverflowHook>, // called when a cell wraps, only with OverflowMode::Wrap
+}
+
+// Builds an InterpreterConfig one setting at a time, starting from the default:
+// 8-bit wrapping cells on a tape that grows to the right, with no limits.
+//
+//     let config = InterpreterConfig::builder().max_steps(1000).wrapping_cells().build();
+#[derive(Clone, Debug, Default)]
+pub struct ConfigBuilder {
+    config: InterpreterConfig,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BoundaryPolicy {
+    Error, // 👈 at cell 0 fails with PointerUnderflow, 👉 grows the tape
+    Wrap,  // the tape is fixed at initial_tape_size cells and both ends wrap around
+    Clamp, // the tape is fixed at initial_tape_size cells and moves stop at either end
+    Grow,  // both ends grow, 👈 at cell 0 moves to cell -1
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OutputMode {
+    Bytes,   // the low byte of the cell value is written as a single raw byte
+    Unicode, // the cell value is a Unicode scalar written as UTF-8, U+FFFD when invalid
+    Decimal, // the cell value is written as a decimal number followed by a space, for debugging
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineEnding {
+    Unchanged, // \n is written as it is, like every other value
+    CrLf,      // \n is written as \r\n
+    Native,    // \r\n on Windows, unchanged everywhere else
+}
+
+impl LineEnding {
+    pub fn is_crlf(self) -> bool {
+        match self {
+            LineEnding::Unchanged => false,
+            LineEnding::CrLf => true,
+            LineEnding::Native => cfg!(windows),
+        }
+    }
+}
+
+// Each ✋ takes the next byte from the reader, so it reads input in order
+// whatever is printed in between. These are the choices for when there is none,
+// Max is the common Brainfuck convention of reading -1 at the end of input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EofPolicy {
+    Zero,      // the cell is set to 0
+    Max,       // the cell is set to the largest value of its width, -1 when signed
+    Unchanged, // the cell keeps its value
+}
+
+// A NUL byte in the output is valid, but cuts text short once it is read as a
+// C string. Printed numbers never contain one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NulPolicy {
+    Emit, // the NUL byte is written like any other
+    Skip, // nothing is written, strict_ascii has nothing to complain about either
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CellWidth {
+    U8,
+    U16,
+    U32,
+}
+
+impl CellWidth {
+    pub fn max_value(self) -> Cell {
+        match self {
+            CellWidth::U8 => u8::MAX.into(),
+            CellWidth::U16 => u16::MAX.into(),
+            CellWidth::U32 => u32::MAX,
+        }
+    }
+
+    // The cell read as a two's complement number of this width.
+    pub fn to_signed(self, cell: Cell) -> i64 {
+        let shift = 64 - 8 * self.bytes() as u32;
+        (i64::from(cell) << shift) >> shift
+    }
+
+    pub fn bytes(self) -> usize {
+        match self {
+            CellWidth::U8 => 1,
+            CellWidth::U16 => 2,
+            CellWidth::U32 => 4,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowMode {
+    Wrap,     // incrementing the largest value gives 0 and decreasing 0 gives the largest value
+    Saturate, // cells stay at 0 or the largest value
+    Error,    // going past either limit fails with CellOverflow
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TapeKind {
+    Contiguous, // a vector holding every cell up to the highest one visited
+    Sparse,     // a map holding only the cells that are not 0
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OutputEvent {
+    pub value: Cell,        // the cell that was printed
+    pub char: Option<char>, // what it was written as, None when it isn't printable
+    pub cursor: isize,      // the memory pointer position
+    pub offset: usize,      // the 👊 in the program
+    pub len: usize,         // how many bytes of output it was written as
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OverflowEvent {
+    pub value: Cell,   // the cell once the whole run of 👆 or 👇 is done
+    pub carry: i64,    // times it wrapped, negative when 👇 went below the lowest value
+    pub cursor: isize, // the memory pointer position
+    pub offset: usize, // the 👆 or 👇 that wrapped first
+}
+
+// A callback receiving events of type T. Clones of a config share the same one.
+pub struct Hook<T>(Arc<dyn Fn(&T) + Send + Sync>);
+
+pub type OutputHook = Hook<OutputEvent>;
+pub type OverflowHook = Hook<OverflowEvent>;
+
+impl<T> Hook<T> {
+    pub fn new(hook: impl Fn(&T) + Send + Sync + 'static) -> Self {
+        Hook(Arc::new(hook))
+    }
+
+    pub fn call(&self, event: &T) {
+        (self.0)(event)
+    }
+}
+
+impl<T> Clone for Hook<T> {
+    fn clone(&self) -> Self {
+        Hook(Arc::clone(&self.0))
+    }
+}
+
+impl<T> fmt::Debug for Hook<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Hook")
+    }
+}
+
+impl InterpreterConfig {
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder::default()
+    }
+}
+
+impl ConfigBuilder {
+    pub fn initial_tape_size(mut self, cells: usize) -> Self {
+        self.config.initial_tape_size = cells;
+        self
+    }
+
+    pub fn max_tape_size(mut self, cells: usize) -> Self {
+        self.config.max_tape_size = Some(cells);
+        self
+    }
+
+    pub fn boundary(mut self, boundary: BoundaryPolicy) -> Self {
+        self.config.boundary = boundary;
+        self
+    }
+
+    pub fn max_steps(mut self, limit: u64) -> Self {
+        self.config.max_steps = Some(limit);
+        self
+    }
+
+    pub fn max_duration(mut self, limit: Duration) -> Self {
+        self.config.max_duration = Some(limit);
+        self
+    }
+
+    pub fn max_output_bytes(mut self, limit: u64) -> Self {
+        self.config.max_output_bytes = Some(limit);
+        self
+    }
+
+    pub fn output(mut self, output: OutputMode) -> Self {
+        self.config.output = output;
+        self
+    }
+
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.config.line_ending = line_ending;
+        self
+    }
+
+    pub fn strict_ascii(mut self, strict: bool) -> Self {
+        self.config.strict_ascii = strict;
+        self
+    }
+
+    pub fn nul(mut self, nul: NulPolicy) -> Self {
+        self.config.nul = nul;
+        self
+    }
+
+    pub fn eof(mut self, eof: EofPolicy) -> Self {
+        self.config.eof = eof;
+        self
+    }
+
+    pub fn flush_every(mut self, prints: u64) -> Self {
+        self.config.flush_every = Some(prints);
+        self
+    }
+
+    pub fn cell_width(mut self, width: CellWidth) -> Self {
+        self.config.cell_width = width;
+        self
+    }
+
+    pub fn initial_cell_value(mut self, value: Cell) -> Self {
+        self.config.initial_cell_value = value;
+        self
+    }
+
+    pub fn overflow(mut self, overflow: OverflowMode) -> Self {
+        self.config.overflow = overflow;
+        self
+    }
+
+    // Same as overflow(OverflowMode::Wrap), which is the default.
+    pub fn wrapping_cells(self) -> Self {
+        self.overflow(OverflowMode::Wrap)
+    }
+
+    pub fn saturating_cells(self) -> Self {
+        self.overflow(OverflowMode::Saturate)
+    }
+
+    pub fn checked_cells(self) -> Self {
+        self.overflow(OverflowMode::Error)
+    }
+
+    pub fn tape(mut self, tape: TapeKind) -> Self {
+        self.config.tape = tape;
+        self
+    }
+
+    pub fn signed(mut self, signed: bool) -> Self {
+        self.config.signed = signed;
+        self
+    }
+
+    pub fn max_loop_depth(mut self, limit: usize) -> Self {
+        self.config.max_loop_depth = Some(limit);
+        self
+    }
+
+    pub fn on_output(mut self, hook: impl Fn(&OutputEvent) + Send + Sync + 'static) -> Self {
+        self.config.on_output = Some(Hook::new(hook));
+        self
+    }
+
+    pub fn on_overflow(mut self, hook: impl Fn(&OverflowEvent) + Send + Sync + 'static) -> Self {
+        self.config.on_overflow = Some(Hook::new(hook));
+        self
+    }
+
+    pub fn build(self) -> InterpreterConfig {
+        self.config
+    }
+}
+
+impl Default for InterpreterConfig {
+    fn default() -> Self {
+        InterpreterConfig {
+            initial_tape_size: 1,
+            max_tape_size: None,
+            boundary: BoundaryPolicy::Error,
+            max_steps: None,
+            max_duration: None,
+            max_output_bytes: None,
+            output: OutputMode::Bytes,
+            line_ending: LineEnding::Unchanged,
+            strict_ascii: false,
+            nul: NulPolicy::Emit,
+            eof: EofPolicy::Zero,
+            flush_every: None,
+            cell_width: CellWidth::U8,
+            initial_cell_value: 0,
+            overflow: OverflowMode::Wrap,
+            tape: TapeKind::Contiguous,
+            signed: false,
+            max_loop_depth: None,
+            on_output: None,
+            on_overflow: None,
+        }
+    }
+}
+
+#[test]
+pub fn test_builder_defaults() -> Result<(), crate::HandError> {
+    let config = InterpreterConfig::builder().build();
+    assert_eq!(
+        alloc::format!("{:?}", config),
+        alloc::format!("{:?}", InterpreterConfig::default())
+    );
+    assert_eq!(config.cell_width, CellWidth::U8);
+    assert_eq!(config.boundary, BoundaryPolicy::Error);
+    assert_eq!(config.overflow, OverflowMode::Wrap);
+    assert_eq!((config.max_steps, config.max_duration), (None, None));
+    assert_eq!(config.max_output_bytes, None);
+    assert_eq!(config.max_tape_size, None);
+
+    let instructions = crate::parse_program(include_str!("../../test2.hand"))?;
+    let mut output = alloc::vec::Vec::new();
+    crate::run_hand_ast_with_config(&config, crate::io::empty(), &mut output, &instructions)?;
+    assert_eq!(output, b"Hello World!\n");
+    Ok(())
+}
+
+#[test]
+pub fn test_builder_overrides() {
+    let config = InterpreterConfig::builder()
+        .max_steps(1000)
+        .max_tape_size(64)
+        .boundary(BoundaryPolicy::Wrap)
+        .cell_width(CellWidth::U16)
+        .eof(EofPolicy::Unchanged)
+        .saturating_cells()
+        .flush_every(8)
+        .on_output(|_| {})
+        .build();
+    assert_eq!(config.max_steps, Some(1000));
+    assert_eq!(config.max_tape_size, Some(64));
+    assert_eq!(config.boundary, BoundaryPolicy::Wrap);
+    assert_eq!(config.cell_width, CellWidth::U16);
+    assert_eq!(config.eof, EofPolicy::Unchanged);
+    assert_eq!(config.overflow, OverflowMode::Saturate);
+    assert_eq!(config.flush_every, Some(8));
+    assert!(config.on_output.is_some());
+    // everything else keeps its default
+    assert_eq!(config.output, OutputMode::Bytes);
+    assert_eq!(config.initial_tape_size, 1);
+
+    let config = InterpreterConfig::builder()
+        .checked_cells()
+        .wrapping_cells()
+        .build();
+    assert_eq!(config.overflow, OverflowMode::Wrap);
+
+    let instructions = crate::parse_program("👇👊").unwrap();
+    let mut output = alloc::vec::Vec::new();
+    let saturating = InterpreterConfig::builder().saturating_cells().build();
+    crate::run_hand_ast_with_config(&saturating, crate::io::empty(), &mut output, &instructions)
+        .unwrap();
+    assert_eq!(output, [0]);
+    let checked = InterpreterConfig::builder().checked_cells().build();
+    let result =
+        crate::run_hand_ast_with_config(&checked, crate::io::empty(), &mut output, &instructions);
+    assert!(matches!(
+        result,
+        Err(crate::HandError::CellOverflow { offset: 0 })
+    ));
+}
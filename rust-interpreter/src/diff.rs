@@ -0,0 +1,189 @@
+use crate::io::{self, Write};
+use crate::{translate, validate, HandError, Instruction, Interpreter, InterpreterConfig};
+use alloc::vec::Vec;
+use core::cell::Cell;
+
+#[derive(Debug)]
+pub enum Comparison {
+    Identical, // the same output, and either both finished or both failed
+    Diverged(Divergence),
+}
+
+#[derive(Debug)]
+pub struct Divergence {
+    pub offset: usize, // index of the first output byte that differs
+    pub left: Side,
+    pub right: Side,
+}
+
+// What one of the two programs did at the point where they diverged.
+#[derive(Debug)]
+pub struct Side {
+    pub error: Option<HandError>, // how it failed, None when it finished
+    pub byte: Option<u8>,         // what it wrote there, None when its output ended first
+    pub steps: u64,               // instructions run up to writing that byte, or in total
+}
+
+// Runs both programs on the same input and compares what they write, such as a
+// program and its optimized version. Every step runs one instruction, so steps
+// count instructions of each program. Output written before an error counts,
+// and a program that may not end should be run with max_steps in the config.
+pub fn compare_programs(
+    config: &InterpreterConfig,
+    left: &[Instruction],
+    right: &[Instruction],
+    input: &[u8],
+) -> Comparison {
+    let left = record(config, left, input);
+    let right = record(config, right, input);
+    let common = left.bytes.len().min(right.bytes.len());
+    let offset = (0..common)
+        .find(|&i| left.bytes[i] != right.bytes[i])
+        .unwrap_or(common);
+    let same_end = left.error.is_some() == right.error.is_some();
+    if offset == common && left.bytes.len() == right.bytes.len() && same_end {
+        return Comparison::Identical;
+    }
+    Comparison::Diverged(Divergence {
+        offset,
+        left: left.side(offset),
+        right: right.side(offset),
+    })
+}
+
+struct Recording {
+    bytes: Vec<u8>,
+    steps: Vec<u64>, // the step that wrote each byte
+    total: u64,
+    error: Option<HandError>,
+}
+
+impl Recording {
+    fn side(self, offset: usize) -> Side {
+        Side {
+            error: self.error,
+            byte: self.bytes.get(offset).copied(),
+            steps: self.steps.get(offset).copied().unwrap_or(self.total),
+        }
+    }
+}
+
+// Keeps every byte with the step that is running while it is written.
+struct Recorder<'s> {
+    bytes: Vec<u8>,
+    steps: Vec<u64>,
+    step: &'s Cell<u64>,
+}
+
+impl Write for Recorder<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.extend_from_slice(buf);
+        self.steps.resize(self.bytes.len(), self.step.get());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn record(config: &InterpreterConfig, instructions: &[Instruction], input: &[u8]) -> Recording {
+    let step = Cell::new(0);
+    let mut recorder = Recorder {
+        bytes: Vec::new(),
+        steps: Vec::new(),
+        step: &step,
+    };
+    let mut total = 0;
+    let error = validate(instructions, config)
+        .and_then(|()| translate(instructions))
+        .and_then(|bytecode| {
+            let mut interpreter = Interpreter::with_config(config, bytecode, input, &mut recorder);
+            let result = loop {
+                step.set(interpreter.steps() + 1);
+                match interpreter.step() {
+                    Ok(Some(_)) => {}
+                    Ok(None) => break Ok(()),
+                    Err(e) => break Err(e),
+                }
+            };
+            total = interpreter.steps();
+            result.and(interpreter.flush_at_end().map_err(HandError::from))
+        })
+        .err();
+    Recording {
+        bytes: recorder.bytes,
+        steps: recorder.steps,
+        total,
+        error,
+    }
+}
+
+#[test]
+pub fn test_compare_optimized_hello_world() {
+    let config = InterpreterConfig::default();
+    let hello = crate::parse_program(include_str!("../../test2.hand")).unwrap();
+    // dead code in front and in the middle for the optimizer to remove
+    let mut padded = crate::parse_program("🤜👊👉🤛👉👈👆👇").unwrap();
+    padded.extend_from_slice(&hello);
+    let optimized = crate::eliminate_dead_code(&padded, &config);
+    assert!(optimized.len() < padded.len());
+
+    assert!(matches!(
+        compare_programs(&config, &padded, &optimized, b""),
+        Comparison::Identical
+    ));
+    assert!(matches!(
+        compare_programs(&config, &hello, &optimized, b""),
+        Comparison::Identical
+    ));
+}
+
+#[test]
+pub fn test_compare_different_programs() {
+    let config = InterpreterConfig::default();
+    let program = |source| crate::parse_program(source).unwrap();
+
+    // the second byte differs, written by the fourth and fifth instruction
+    let Comparison::Diverged(divergence) =
+        compare_programs(&config, &program("👆👆👊👊"), &program("👆👆👊👆👊"), b"")
+    else {
+        panic!("expected the programs to diverge");
+    };
+    assert_eq!(divergence.offset, 1);
+    assert_eq!(divergence.left.byte, Some(2));
+    assert_eq!(divergence.right.byte, Some(3));
+    assert_eq!((divergence.left.steps, divergence.right.steps), (4, 5));
+
+    // one output ends first, and the same output with an error is different too
+    let Comparison::Diverged(divergence) =
+        compare_programs(&config, &program("✋👊"), &program("✋👊✋👊"), b"ab")
+    else {
+        panic!("expected the programs to diverge");
+    };
+    assert_eq!(divergence.offset, 1);
+    assert_eq!((divergence.left.byte, divergence.left.steps), (None, 2));
+    assert_eq!(
+        (divergence.right.byte, divergence.right.steps),
+        (Some(b'b'), 4)
+    );
+
+    let Comparison::Diverged(divergence) =
+        compare_programs(&config, &program("👆👊"), &program("👆👊👈👈"), b"")
+    else {
+        panic!("expected the programs to diverge");
+    };
+    assert_eq!(divergence.offset, 1);
+    assert!(divergence.left.error.is_none());
+    assert!(matches!(
+        divergence.right.error,
+        Some(HandError::PointerUnderflow { offset: 2 })
+    ));
+
+    // only whether they failed is compared, not how
+    let unmatched = [Instruction::LoopStart];
+    assert!(matches!(
+        compare_programs(&config, &unmatched, &unmatched, b""),
+        Comparison::Identical
+    ));
+}
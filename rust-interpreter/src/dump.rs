@@ -0,0 +1,235 @@
+use crate::emit::to_emoji;
+use crate::tape::initial_cell;
+use crate::{Cell, CellWidth, Instruction, InterpreterConfig, MachineState, StepResult, Tape};
+use alloc::borrow::Cow;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::fmt::Write;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryView<'a> {
+    pub cells: Cow<'a, [Cell]>,
+    pub cursor: usize,
+    pub width: CellWidth,
+    pub signed: bool,     // show cell values as two's complement numbers
+    pub gaps: Vec<usize>, // indexes of the cells with blank cells left out in front of them
+}
+
+// More blank cells in a row than this between two cells a MemoryView of a
+// sparse tape shows are left out.
+const MAX_BLANK_RUN: usize = 8;
+
+impl<'a> MemoryView<'a> {
+    // All cells of a tape that holds every one of them. Of a sparse tape only
+    // the cells it stores, the one at the pointer and both ends, and the blank
+    // cells between them unless there are more than MAX_BLANK_RUN in a row.
+    pub(crate) fn of(tape: &'a dyn Tape, config: &InterpreterConfig) -> Self {
+        let first = tape.first_cell();
+        let mut shown = tape.touched();
+        let (cells, cursor, gaps) = if shown.len() == tape.cell_count() {
+            (tape.cells(), (tape.cursor() - first) as usize, Vec::new())
+        } else {
+            let blank = initial_cell(config);
+            let last = first + tape.cell_count() as isize - 1;
+            shown.extend([(first, blank), (last, blank), (tape.cursor(), tape.get())]);
+            // the sort is stable, so the stored value of a cell comes first and stays
+            shown.sort_by_key(|&(position, _)| position);
+            shown.dedup_by_key(|&mut (position, _)| position);
+            let (mut cells, mut cursor, mut gaps) = (Vec::new(), 0, Vec::new());
+            let mut next = first;
+            for (position, value) in shown {
+                let blanks = (position - next) as usize;
+                if blanks > MAX_BLANK_RUN {
+                    gaps.push(cells.len());
+                } else {
+                    cells.resize(cells.len() + blanks, blank);
+                }
+                if position == tape.cursor() {
+                    cursor = cells.len();
+                }
+                cells.push(value);
+                next = position + 1;
+            }
+            (Cow::Owned(cells), cursor, gaps)
+        };
+        MemoryView {
+            cells,
+            cursor,
+            width: config.cell_width,
+            signed: config.signed,
+            gaps,
+        }
+    }
+
+    // Wider cells are dumped as their big-endian bytes. The offsets count the
+    // cells shown, not the gaps.
+    pub fn hex_dump(&self) -> String {
+        hex_dump(&cell_bytes(&self.cells, self.width))
+    }
+}
+
+fn cell_bytes(cells: &[Cell], width: CellWidth) -> Vec<u8> {
+    cells
+        .iter()
+        .flat_map(|cell| cell.to_be_bytes().into_iter().skip(4 - width.bytes()))
+        .collect()
+}
+
+// Cell values separated by spaces, with the one at the pointer in brackets and
+// `...` for each gap.
+impl fmt::Display for MemoryView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, cell) in self.cells.iter().enumerate() {
+            if index > 0 {
+                f.write_char(' ')?;
+            }
+            if self.gaps.binary_search(&index).is_ok() {
+                f.write_str("... ")?;
+            }
+            let value = if self.signed {
+                self.width.to_signed(*cell)
+            } else {
+                i64::from(*cell)
+            };
+            if index == self.cursor {
+                write!(f, "[{}]", value)?;
+            } else {
+                write!(f, "{}", value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Formats cells the way `xxd` does: offset, sixteen cells in groups of two, and
+// the printable ASCII characters with `.` for everything else.
+pub fn hex_dump(cells: &[u8]) -> String {
+    let mut dump = String::new();
+    for (row, chunk) in cells.chunks(16).enumerate() {
+        hex_row(&mut dump, row * 16, chunk);
+    }
+    dump
+}
+
+fn hex_row(dump: &mut String, offset: usize, chunk: &[u8]) {
+    let _ = write!(dump, "{:08x}:", offset);
+    for i in 0..16 {
+        if i % 2 == 0 {
+            dump.push(' ');
+        }
+        match chunk.get(i) {
+            Some(b) => {
+                let _ = write!(dump, "{:02x}", b);
+            }
+            None => dump.push_str("  "),
+        }
+    }
+    dump.push_str("  ");
+    dump.extend(chunk.iter().map(|&b| match b {
+        0x20..=0x7e => b as char,
+        _ => '.',
+    }));
+    dump.push('\n');
+}
+
+// The machine after a run: where the pointer ended up, how many instructions it
+// ran and the whole tape. Every turn of a loop counts its instructions, also
+// when compiled bytecode ran it as one op. The hex offsets count bytes from the
+// first cell, which is below cell 0 once the tape grew left. Like `xxd -a`
+// does, a `*` stands for rows left out, here the rows of blank cells a sparse
+// tape never stored.
+pub fn dump_state(state: &MachineState, width: CellWidth) -> String {
+    let per_row = 16 / width.bytes();
+    let mut rows = BTreeMap::new();
+    for &(position, value) in &state.cells {
+        let index = (position - state.first_cell) as usize;
+        let row = index / per_row;
+        let len = per_row.min(state.cell_count - row * per_row);
+        rows.entry(row).or_insert_with(|| vec![state.blank; len])[index % per_row] = value;
+    }
+    let mut dump = format!(
+        "cursor: {}\ninstructions executed: {}\nfirst cell: {}\n",
+        state.cursor, state.steps, state.first_cell
+    );
+    let mut next = 0;
+    for (row, cells) in rows {
+        if row > next {
+            dump.push_str("*\n");
+        }
+        hex_row(
+            &mut dump,
+            row * per_row * width.bytes(),
+            &cell_bytes(&cells, width),
+        );
+        next = row + 1;
+    }
+    if next * per_row < state.cell_count {
+        dump.push_str("*\n");
+    }
+    dump
+}
+
+// One line of a trace: where the instruction is in the program, the hand
+// itself, and the pointer and its cell once it ran. The step has to come from
+// a translated program, where every op is the instruction at the same offset.
+pub fn trace_line(instruction: Instruction, step: &StepResult) -> String {
+    format!(
+        "{:>6} {} cursor {} cell {}",
+        step.offset,
+        to_emoji(instruction),
+        step.cursor,
+        step.cell
+    )
+}
+
+#[test]
+pub fn test_hex_dump() {
+    let cells = b"Hello, World!\n\x00\xff\x01";
+
+    assert_eq!(
+        hex_dump(cells),
+        "00000000: 4865 6c6c 6f2c 2057 6f72 6c64 210a 00ff  Hello, World!...\n\
+         00000010: 01                                       .\n"
+    );
+}
+
+#[test]
+pub fn test_dump_state() {
+    let state = MachineState {
+        cells: vec![(-1, 0), (0, 72), (1, 105), (2, 0x1ff)],
+        blank: 0,
+        first_cell: -1,
+        cell_count: 4,
+        cursor: 1,
+        steps: 24,
+    };
+
+    assert_eq!(
+        dump_state(&state, CellWidth::U8),
+        "cursor: 1\ninstructions executed: 24\nfirst cell: -1\n\
+         00000000: 0048 69ff                                .Hi.\n"
+    );
+    assert!(dump_state(&state, CellWidth::U16)
+        .ends_with("00000000: 0000 0048 0069 01ff                      ...H.i..\n"));
+}
+
+#[test]
+pub fn test_trace_line() -> Result<(), crate::HandError> {
+    let instructions = crate::parse_program("👆👉")?;
+    let mut lines = Vec::new();
+    let mut interpreter =
+        crate::Interpreter::new(&instructions, crate::io::empty(), crate::io::sink())?;
+    interpreter.set_trace(|step| lines.push(trace_line(instructions[step.offset], step)));
+    interpreter.run()?;
+    drop(interpreter);
+
+    assert_eq!(
+        lines,
+        ["     0 👆 cursor 0 cell 1", "     1 👉 cursor 1 cell 0"]
+    );
+    Ok(())
+}
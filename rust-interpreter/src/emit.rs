@@ -0,0 +1,405 @@
+use crate::{Instruction, Node};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+#[derive(Copy, Clone, Debug)]
+pub struct FormatOptions {
+    pub indent: usize,    // spaces added for every level of loop nesting
+    pub group_runs: bool, // separate runs of different instructions with a space
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent: 2,
+            group_runs: true,
+        }
+    }
+}
+
+pub fn to_brainfuck(instructions: &[Instruction]) -> String {
+    instructions
+        .iter()
+        .map(|ins| match ins {
+            Instruction::Next => '>',
+            Instruction::Previous => '<',
+            Instruction::Increment => '+',
+            Instruction::Decrease => '-',
+            Instruction::LoopStart => '[',
+            Instruction::LoopEnd => ']',
+            Instruction::Print => '.',
+            Instruction::Input => ',',
+        })
+        .collect()
+}
+
+// Standalone C program with a fixed tape of 30000 byte cells. Reading past the
+// end of input stores 0, like the interpreter does by default.
+pub fn to_c(instructions: &[Instruction]) -> String {
+    let mut output = String::from(
+        "#include <stdio.h>\n\nunsigned char tape[30000];\n\nint main(void) {\n    unsigned char *p = tape;\n",
+    );
+    if instructions.contains(&Instruction::Input) {
+        output.push_str("    int c;\n");
+    }
+    let mut depth = 1usize;
+
+    for &ins in instructions {
+        if ins == Instruction::LoopEnd {
+            depth = depth.saturating_sub(1).max(1);
+        }
+        let line = match ins {
+            Instruction::Next => "++p;",
+            Instruction::Previous => "--p;",
+            Instruction::Increment => "++*p;",
+            Instruction::Decrease => "--*p;",
+            Instruction::LoopStart => "while (*p) {",
+            Instruction::LoopEnd => "}",
+            Instruction::Print => "putchar(*p);",
+            Instruction::Input => "*p = (c = getchar()) == EOF ? 0 : c;",
+        };
+        output.push_str(&"    ".repeat(depth));
+        output.push_str(line);
+        output.push('\n');
+        if ins == Instruction::LoopStart {
+            depth += 1;
+        }
+    }
+    output.push_str("    return 0;\n}\n");
+
+    output
+}
+
+// One hand per instruction and nothing else, parse_hand_code turns it back
+// into the same instructions.
+pub fn to_hand_string(instructions: &[Instruction]) -> String {
+    instructions.iter().map(|&ins| to_emoji(ins)).collect()
+}
+
+pub(crate) fn to_emoji(ins: Instruction) -> char {
+    match ins {
+        Instruction::Next => '👉',
+        Instruction::Previous => '👈',
+        Instruction::Increment => '👆',
+        Instruction::Decrease => '👇',
+        Instruction::LoopStart => '🤜',
+        Instruction::LoopEnd => '🤛',
+        Instruction::Print => '👊',
+        Instruction::Input => '✋',
+    }
+}
+
+// One line for every instruction saying what it does, indented two spaces for
+// every loop it is in, for reading a program without knowing the hands yet.
+pub fn explain(instructions: &[Instruction]) -> String {
+    let mut text = String::new();
+    for (_, ins, depth) in crate::iter_with_depth(instructions) {
+        let effect = match ins {
+            Instruction::Next => "move right",
+            Instruction::Previous => "move left",
+            Instruction::Increment => "increment cell",
+            Instruction::Decrease => "decrement cell",
+            Instruction::LoopStart => "loop while nonzero",
+            Instruction::LoopEnd => "end loop",
+            Instruction::Print => "print cell",
+            Instruction::Input => "read a byte into cell",
+        };
+        text.extend(core::iter::repeat_n(' ', 2 * depth));
+        text.push(to_emoji(ins));
+        text.push(' ');
+        text.push_str(effect);
+        text.push('\n');
+    }
+    text
+}
+
+// Puts every loop boundary on its own line and indents loop bodies one level
+// deeper than their surroundings. The output parses back to the same program.
+pub fn format_hand(instructions: &[Instruction], options: FormatOptions) -> String {
+    let nodes: Vec<_> = instructions
+        .iter()
+        .map(|&ins| Node::Instruction(ins))
+        .collect();
+    format_hand_nodes(&nodes, options)
+}
+
+// Same as format_hand for the nodes of parse_program_nodes, keeping the
+// comments. A comment that had a line to itself still has one, indented like
+// the code around it, and any other comment stays at the end of the line of
+// the instruction before it. Other whitespace is replaced.
+pub fn format_hand_nodes(nodes: &[Node], options: FormatOptions) -> String {
+    let mut output = String::new();
+    let mut line = String::new();
+    let mut depth = 0usize;
+    let mut previous = None;
+    let mut own_line = true; // nothing but whitespace since the last line break
+
+    let flush = |output: &mut String, line: &mut String, depth: usize| {
+        if !line.is_empty() {
+            output.push_str(&" ".repeat(depth * options.indent));
+            output.push_str(line);
+            output.push('\n');
+            line.clear();
+        }
+    };
+
+    for node in nodes {
+        let ins = match node {
+            Node::Whitespace(space) => {
+                own_line |= space.contains('\n');
+                continue;
+            }
+            Node::Comment(comment) if own_line || output.is_empty() && line.is_empty() => {
+                flush(&mut output, &mut line, depth);
+                flush(&mut output, &mut comment.clone(), depth);
+                previous = None;
+                continue;
+            }
+            Node::Comment(comment) if line.is_empty() => {
+                // after a loop boundary, which has its line to itself
+                output.pop();
+                output.push(' ');
+                output.push_str(comment);
+                output.push('\n');
+                continue;
+            }
+            Node::Comment(comment) => {
+                line.push(' ');
+                line.push_str(comment);
+                flush(&mut output, &mut line, depth);
+                previous = None;
+                continue;
+            }
+            Node::Instruction(ins) => *ins,
+        };
+        own_line = false;
+        match ins {
+            Instruction::LoopStart => {
+                flush(&mut output, &mut line, depth);
+                flush(&mut output, &mut to_emoji(ins).to_string(), depth);
+                depth += 1;
+                previous = None;
+            }
+            Instruction::LoopEnd => {
+                flush(&mut output, &mut line, depth);
+                depth = depth.saturating_sub(1);
+                flush(&mut output, &mut to_emoji(ins).to_string(), depth);
+                previous = None;
+            }
+            _ => {
+                if options.group_runs && previous.is_some() && previous != Some(ins) {
+                    line.push(' ');
+                }
+                line.push(to_emoji(ins));
+                previous = Some(ins);
+            }
+        }
+    }
+    flush(&mut output, &mut line, depth);
+
+    output
+}
+
+// The instructions as a JSON array of their names, such as ["Increment","Print"],
+// for tools that don't read hands.
+#[cfg(feature = "serde")]
+pub fn to_json(instructions: &[Instruction]) -> String {
+    serde_json::to_string(instructions).expect("instructions always serialize")
+}
+
+// Like to_json with one object per instruction, holding its name and where it
+// is in the source, such as {"instruction":"Print","offset":4,"line":1,"column":2}.
+#[cfg(feature = "serde")]
+pub fn to_json_spanned(instructions: &[(Instruction, crate::Span)]) -> String {
+    #[derive(serde::Serialize)]
+    struct Spanned {
+        instruction: Instruction,
+        #[serde(flatten)]
+        span: crate::Span,
+    }
+    let spanned: Vec<_> = instructions
+        .iter()
+        .map(|&(instruction, span)| Spanned { instruction, span })
+        .collect();
+    serde_json::to_string(&spanned).expect("instructions always serialize")
+}
+
+#[test]
+pub fn test_hello_world_to_brainfuck() {
+    let code = include_str!("../../test2.hand").trim();
+    let (_, instructions) = crate::parse_hand_code(code).unwrap();
+
+    let brainfuck = to_brainfuck(&instructions);
+
+    assert_eq!(
+        brainfuck,
+        ">++++++++[-<+++++++++>]<.>>+>-[+]++>++>+++[>[->+++<<+++>]<<]>-----.>->+++..+++.>-.<<+[>[+>+]>>]<--------------.>>.+++.------.--------.>+.>+."
+    );
+    assert_eq!(brainfuck.chars().count(), instructions.len());
+}
+
+#[test]
+pub fn test_format_hello_world() {
+    let code = include_str!("../../test2.hand").trim();
+    let (_, instructions) = crate::parse_hand_code(code).unwrap();
+    let options = FormatOptions::default();
+
+    let formatted = format_hand(&instructions, options);
+
+    let mut depth = 0;
+    for line in formatted.lines() {
+        let body = line.trim_start();
+        if body.starts_with('🤛') {
+            depth -= 1;
+        }
+        assert_eq!(
+            line.len() - body.len(),
+            depth * options.indent,
+            "{:?}",
+            line
+        );
+        if body.starts_with('🤜') {
+            depth += 1;
+        }
+    }
+    assert_eq!(depth, 0);
+    assert!(formatted.starts_with("👉 👆👆👆👆👆👆👆👆\n🤜\n  👇 👈 👆👆👆👆👆👆👆👆👆 👉\n🤛\n"));
+
+    let (_, parsed) = crate::parse_hand_code(&formatted).unwrap();
+    assert_eq!(parsed, instructions);
+
+    let ungrouped = format_hand(
+        &instructions,
+        FormatOptions {
+            indent: 4,
+            group_runs: false,
+        },
+    );
+    assert!(ungrouped.starts_with("👉👆👆👆👆👆👆👆👆\n🤜\n    👇👈👆👆👆👆👆👆👆👆👆👉\n🤛\n"));
+}
+
+#[test]
+pub fn test_hello_world_to_c() {
+    let code = include_str!("../../test2.hand").trim();
+    let (_, instructions) = crate::parse_hand_code(code).unwrap();
+
+    let c = to_c(&instructions);
+
+    assert!(c.starts_with("#include <stdio.h>\n"));
+    assert!(c.contains("int main(void) {\n"));
+    assert!(c.contains("    while (*p) {\n        --*p;\n        --p;\n"));
+    assert!(c.contains("putchar(*p);"));
+    assert!(c.ends_with("    return 0;\n}\n"));
+    assert_eq!(c.matches('{').count(), c.matches('}').count());
+    assert_eq!(
+        c.matches("while (*p) {").count(),
+        instructions
+            .iter()
+            .filter(|&&ins| ins == Instruction::LoopStart)
+            .count()
+    );
+}
+
+#[test]
+pub fn test_hand_string_round_trip() {
+    use Instruction::*;
+    let all = [
+        Next, Previous, Increment, Decrease, LoopStart, LoopEnd, Print, Input,
+    ];
+    let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+
+    for _ in 0..1000 {
+        let len = (next() % 64) as usize;
+        let instructions: Vec<_> = (0..len).map(|_| all[(next() % 8) as usize]).collect();
+
+        let source = to_hand_string(&instructions);
+
+        assert_eq!(source.chars().count(), len);
+        assert_eq!(crate::parse_hand_code(&source), Ok(("", instructions)));
+    }
+    assert_eq!(to_hand_string(&all), "👉👈👆👇🤜🤛👊✋");
+}
+
+#[test]
+pub fn test_format_keeps_comments() -> Result<(), crate::HandError> {
+    let source = "# prints 2\n👆👆 # two\n🤜 # loop\n  👇👉👆👈\n  # moved\n🤛👉👊";
+
+    let nodes = crate::parse_program_nodes(source)?;
+    let formatted = format_hand_nodes(&nodes, FormatOptions::default());
+
+    assert_eq!(
+        formatted,
+        "# prints 2\n👆👆 # two\n🤜 # loop\n  👇 👉 👆 👈\n  # moved\n🤛\n👉 👊\n"
+    );
+    let comments = |nodes: &[crate::Node]| {
+        nodes
+            .iter()
+            .filter(|node| matches!(node, crate::Node::Comment(_)))
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let reparsed = crate::parse_program_nodes(&formatted)?;
+    assert_eq!(comments(&reparsed), comments(&nodes));
+    assert_eq!(
+        crate::parse_program(&formatted)?,
+        crate::parse_program(source)?
+    );
+    assert_eq!(
+        format_hand_nodes(&reparsed, FormatOptions::default()),
+        formatted
+    );
+
+    assert!(matches!(
+        crate::parse_program_nodes("👆 x"),
+        Err(crate::HandError::Parse { offset: 5, .. })
+    ));
+
+    Ok(())
+}
+
+#[cfg(feature = "serde")]
+#[test]
+pub fn test_to_json() {
+    let (_, instructions) = crate::parse_hand_code("👆👆🤜👇🤛👊").unwrap();
+    assert_eq!(
+        to_json(&instructions),
+        r#"["Increment","Increment","LoopStart","Decrease","LoopEnd","Print"]"#
+    );
+    assert_eq!(to_json(&[]), "[]");
+
+    let (_, spanned) = crate::parse_hand_code_spanned("👆\n 👊").unwrap();
+    assert_eq!(
+        to_json_spanned(&spanned),
+        concat!(
+            r#"[{"instruction":"Increment","offset":0,"line":1,"column":1},"#,
+            r#"{"instruction":"Print","offset":6,"line":2,"column":2}]"#
+        )
+    );
+}
+
+#[test]
+pub fn test_explain() -> Result<(), crate::HandError> {
+    let instructions = crate::parse_program("✋🤜👉👆👈👇🤛👉👊")?;
+
+    let expected = [
+        "✋ read a byte into cell",
+        "🤜 loop while nonzero",
+        "  👉 move right",
+        "  👆 increment cell",
+        "  👈 move left",
+        "  👇 decrement cell",
+        "🤛 end loop",
+        "👉 move right",
+        "👊 print cell",
+    ];
+    assert_eq!(explain(&instructions).lines().collect::<Vec<_>>(), expected);
+    assert!(explain(&instructions).ends_with('\n'));
+    assert_eq!(explain(&[]), "");
+    Ok(())
+}
@@ -0,0 +1,1987 @@
+use crate::bytecode::{translate_unresolved, unresolved_op};
+use crate::io::{self, Read, Write};
+use crate::tape::{initial_cell, new_tape};
+use crate::{translate, Bytecode, HandError, Instruction, InterpreterConfig, MachineState};
+use crate::{BoundaryPolicy, IndexPolicy, NulPolicy, Tape, TapeError};
+use crate::{
+    Cell, EofPolicy, MemoryView, Op, OutputEvent, OutputMode, OverflowEvent, OverflowMode,
+};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ops::Range;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+// How many ops go by between two looks at the clock for max_duration. Reading
+// the clock costs about as much as a few dozen ops, so checking less often
+// keeps the overhead small, at the price of overshooting the limit by up to
+// this many ops.
+#[cfg(feature = "std")]
+const CLOCK_INTERVAL: u64 = 1024;
+
+type Trace<'a> = Box<dyn FnMut(&StepResult) + Send + 'a>;
+
+pub struct Interpreter<'a, R, W> {
+    config: InterpreterConfig,
+    bytecode: Cow<'a, Bytecode>,
+    reader: R,
+    writer: W,
+    tape: Box<dyn Tape + 'a>,
+    flow_offset: usize,
+    loop_step: usize, // instructions of the op at flow_offset that already ran
+    steps: u64,       // instructions run, each turn of a fused loop included
+    prints: u64,      // 👊 executed, for flush_every
+    written: u64,     // bytes of output, for max_output_bytes
+    limit_from: u64,  // max_steps counts the steps taken since this many
+    #[cfg(feature = "std")]
+    started: Option<Instant>, // when max_duration started counting
+    #[cfg(feature = "std")]
+    until_clock: u64, // ops to go before max_duration reads the clock again
+    breakpoints: BTreeSet<usize>,
+    lazy_jumps: Option<BTreeMap<usize, usize>>, // loops matched so far, None when the ops have their targets
+    trace: Option<Trace<'a>>,
+    profile: Option<Vec<u64>>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct StepResult {
+    pub offset: usize, // index of the op that was executed
+    pub op: Op,
+    pub cursor: isize, // memory pointer position after the op
+    pub cell: Cell,    // value of the memory cell at the pointer after the op
+}
+
+// Everything restore needs to put an interpreter back where snapshot was taken.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    pub cells: Vec<(isize, Cell)>, // the cells the tape holds, see Tape::touched
+    pub first_cell: isize,         // lowest position on the tape
+    pub cell_count: usize,         // how many positions the tape spans from first_cell on
+    pub cursor: isize,             // the memory pointer position
+    pub flow_offset: usize,        // the op that runs next
+    pub loop_step: usize,          // how many instructions of that op already ran
+    pub steps: u64,
+}
+
+impl<'a> From<Bytecode> for Cow<'a, Bytecode> {
+    fn from(bytecode: Bytecode) -> Self {
+        Cow::Owned(bytecode)
+    }
+}
+
+impl<'a> From<&'a Bytecode> for Cow<'a, Bytecode> {
+    fn from(bytecode: &'a Bytecode) -> Self {
+        Cow::Borrowed(bytecode)
+    }
+}
+
+impl<'a, R: Read, W: Write> Interpreter<'a, R, W> {
+    // Every instruction becomes its own op, so each step runs exactly one instruction.
+    pub fn new(instructions: &[Instruction], reader: R, writer: W) -> Result<Self, HandError> {
+        let bytecode = translate(instructions)?;
+        Ok(Self::with_config(
+            &InterpreterConfig::default(),
+            bytecode,
+            reader,
+            writer,
+        ))
+    }
+
+    // The tape is picked from the config, see with_tape to bring your own.
+    pub fn with_config(
+        config: &InterpreterConfig,
+        bytecode: impl Into<Cow<'a, Bytecode>>,
+        reader: R,
+        writer: W,
+    ) -> Self {
+        Self::build(config, bytecode.into(), new_tape(config), reader, writer)
+    }
+
+    // Runs on the given tape, the boundary, tape and size settings of the config are not used.
+    pub fn with_tape(
+        config: &InterpreterConfig,
+        bytecode: impl Into<Cow<'a, Bytecode>>,
+        tape: impl Tape + 'a,
+        reader: R,
+        writer: W,
+    ) -> Self {
+        Self::build(config, bytecode.into(), Box::new(tape), reader, writer)
+    }
+
+    // Loops are matched while running instead of up front: the first time a
+    // jump is taken the ops are searched for its other end, which is remembered
+    // along with every loop found on the way. Loops that never jump cost
+    // nothing, but an unmatched loop only fails once a jump needs its other end,
+    // see calc_wormholes to check the whole program first.
+    pub fn with_lazy_jumps(
+        config: &InterpreterConfig,
+        instructions: &[Instruction],
+        reader: R,
+        writer: W,
+    ) -> Self {
+        let bytecode = translate_unresolved(instructions);
+        let mut interpreter = Self::with_config(config, bytecode, reader, writer);
+        interpreter.lazy_jumps = Some(BTreeMap::new());
+        interpreter
+    }
+
+    fn build(
+        config: &InterpreterConfig,
+        bytecode: Cow<'a, Bytecode>,
+        tape: Box<dyn Tape + 'a>,
+        reader: R,
+        writer: W,
+    ) -> Self {
+        Interpreter {
+            config: config.clone(),
+            bytecode,
+            reader,
+            writer,
+            tape,
+            flow_offset: 0,
+            loop_step: 0,
+            steps: 0,
+            prints: 0,
+            written: 0,
+            limit_from: 0,
+            #[cfg(feature = "std")]
+            started: None,
+            #[cfg(feature = "std")]
+            until_clock: 0,
+            breakpoints: BTreeSet::new(),
+            lazy_jumps: None,
+            trace: None,
+            profile: None,
+        }
+    }
+
+    // Goes back to the first op, the tape and the step count stay as they are.
+    pub fn rewind(&mut self) {
+        self.flow_offset = 0;
+        self.loop_step = 0;
+    }
+
+    // Starts over on a fresh tape from the config, which also replaces a tape
+    // given to with_tape. Breakpoints, the trace and the profile are kept.
+    pub fn reset(&mut self) {
+        self.tape = new_tape(&self.config);
+        self.flow_offset = 0;
+        self.loop_step = 0;
+        self.steps = 0;
+        self.prints = 0;
+        self.written = 0;
+        self.limit_from = 0;
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            cells: self.tape.touched(),
+            first_cell: self.tape.first_cell(),
+            cell_count: self.tape.cell_count(),
+            cursor: self.tape.cursor(),
+            flow_offset: self.flow_offset,
+            loop_step: self.loop_step,
+            steps: self.steps,
+        }
+    }
+
+    // Goes back, or forward, to a snapshot of this interpreter. The tape is
+    // rebuilt from the config like reset does, so this fails only for a
+    // snapshot whose tape the config can't hold. Limits keep counting from
+    // where the current run started, or from the snapshot if that is earlier.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), TapeError> {
+        let span = snapshot.first_cell..snapshot.first_cell + snapshot.cell_count as isize;
+        self.load_tape(snapshot.cells.iter().copied(), span, snapshot.cursor)?;
+        self.flow_offset = snapshot.flow_offset;
+        self.loop_step = snapshot.loop_step;
+        self.steps = snapshot.steps;
+        self.limit_from = self.limit_from.min(snapshot.steps);
+        Ok(())
+    }
+
+    // Replaces the tape with a new one from the config that spans at least span,
+    // holding the given cells by position, with the pointer at cursor. Every
+    // other cell is blank.
+    pub(crate) fn load_tape(
+        &mut self,
+        cells: impl IntoIterator<Item = (isize, Cell)>,
+        span: Range<isize>,
+        cursor: isize,
+    ) -> Result<(), TapeError> {
+        let mut tape = new_tape(&self.config);
+        if !span.is_empty() {
+            move_to(&mut *tape, span.start)?;
+            move_to(&mut *tape, span.end - 1)?;
+        }
+        for (position, value) in cells {
+            move_to(&mut *tape, position)?;
+            tape.set(value);
+        }
+        move_to(&mut *tape, cursor)?;
+        self.tape = tape;
+        Ok(())
+    }
+
+    pub fn set_breakpoint(&mut self, offset: usize) {
+        self.breakpoints.insert(offset);
+    }
+
+    pub fn clear_breakpoint(&mut self, offset: usize) {
+        self.breakpoints.remove(&offset);
+    }
+
+    pub fn breakpoints(&self) -> &BTreeSet<usize> {
+        &self.breakpoints
+    }
+
+    // Called after every executed op, whether driven by step, run or run_until_breakpoint.
+    pub fn set_trace(&mut self, trace: impl FnMut(&StepResult) + Send + 'a) {
+        self.trace = Some(Box::new(trace));
+    }
+
+    pub fn clear_trace(&mut self) {
+        self.trace = None;
+    }
+
+    // Starts counting how many times each op runs, see profile.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(vec![0; self.bytecode.ops.len()]);
+    }
+
+    // Execution counts indexed by op offset, None unless profiling is enabled.
+    pub fn profile(&self) -> Option<&[u64]> {
+        self.profile.as_deref()
+    }
+
+    // Offsets of the instructions whose op never ran, None unless profiling is
+    // enabled. Every op stands for its first instruction, so with the bytecode
+    // from translate this is every instruction that never ran, while compiled
+    // bytecode only reports where each fused op starts.
+    pub fn uncovered(&self) -> Option<BTreeSet<usize>> {
+        let profile = self.profile.as_ref()?;
+        let offsets = &self.bytecode.offsets;
+        Some(
+            profile
+                .iter()
+                .zip(offsets)
+                .filter_map(|(&count, &offset)| (count == 0).then_some(offset))
+                .collect(),
+        )
+    }
+
+    pub fn tape(&self) -> &dyn Tape {
+        self.tape.as_ref()
+    }
+
+    // Every cell the tape spans, see memory for a view that leaves out the long
+    // runs of blank cells a sparse tape doesn't store.
+    pub fn buffer(&self) -> Cow<'_, [Cell]> {
+        self.tape.cells()
+    }
+
+    pub fn memory(&self) -> MemoryView<'_> {
+        MemoryView::of(self.tape.as_ref(), &self.config)
+    }
+
+    pub fn cursor(&self) -> isize {
+        self.tape.cursor()
+    }
+
+    // The cell offset positions right of the pointer, left when negative, see
+    // read_at for absolute positions. The pointer ends up where it was, but
+    // looking past either end grows the tape like moving there would.
+    pub fn peek(&mut self, offset: isize) -> Result<Cell, TapeError> {
+        self.at_offset(offset, |tape| tape.get())
+    }
+
+    // Values are cut down to the configured cell width.
+    pub fn poke(&mut self, offset: isize, value: Cell) -> Result<(), TapeError> {
+        let value = value & self.config.cell_width.max_value();
+        self.at_offset(offset, |tape| tape.set(value))
+    }
+
+    // The cell at an absolute position, with what happens to positions outside
+    // the tape spelled out by policy rather than left to the boundary. The
+    // pointer stays where it is.
+    pub fn read_at(&mut self, index: isize, policy: IndexPolicy) -> Result<Cell, TapeError> {
+        let index = self.resolve_index(index, policy)?;
+        self.peek(index - self.tape.cursor())
+    }
+
+    pub fn write_at(
+        &mut self,
+        index: isize,
+        value: Cell,
+        policy: IndexPolicy,
+    ) -> Result<(), TapeError> {
+        let index = self.resolve_index(index, policy)?;
+        self.poke(index - self.tape.cursor(), value)
+    }
+
+    fn resolve_index(&self, index: isize, policy: IndexPolicy) -> Result<isize, TapeError> {
+        let first = self.tape.first_cell();
+        let count = self.tape.cell_count() as isize;
+        match policy {
+            IndexPolicy::Grow => Ok(index),
+            IndexPolicy::Error if (first..first + count).contains(&index) => Ok(index),
+            IndexPolicy::Error => Err(TapeError::OutOfRange { index }),
+            IndexPolicy::Wrap => Ok(first + (index - first).rem_euclid(count)),
+        }
+    }
+
+    fn at_offset<T>(
+        &mut self,
+        offset: isize,
+        f: impl FnOnce(&mut dyn Tape) -> T,
+    ) -> Result<T, TapeError> {
+        let cursor = self.tape.cursor();
+        let n = offset.unsigned_abs();
+        if offset < 0 {
+            self.tape.move_left(n)?;
+        } else {
+            self.tape.move_right(n)?;
+        }
+        let result = f(&mut *self.tape);
+        // the cells on the way back are all there already, so this can't fail, and
+        // going by position also comes back from a move a clamped tape cut short
+        move_to(&mut *self.tape, cursor)?;
+        Ok(result)
+    }
+
+    pub fn flow_offset(&self) -> usize {
+        self.flow_offset
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn into_state(self) -> MachineState {
+        MachineState {
+            cells: self.tape.touched(),
+            blank: initial_cell(&self.config),
+            first_cell: self.tape.first_cell(),
+            cell_count: self.tape.cell_count(),
+            cursor: self.tape.cursor(),
+            steps: self.steps,
+        }
+    }
+
+    // Every call gets max_steps more steps and max_duration more time. Running
+    // out leaves the program where it stopped, so calling run again resumes it.
+    // Nothing is reset, see rewind and reset to run the program again.
+    pub fn run(&mut self) -> Result<(), HandError> {
+        self.limit_from = self.steps;
+        #[cfg(feature = "std")]
+        {
+            self.started = None;
+            self.until_clock = 0;
+        }
+        let result = self.run_ops().map(|_| ());
+        // whatever was printed before an error is worth seeing too
+        let flushed = self.flush_at_end().map_err(HandError::from);
+        result.and(flushed)
+    }
+
+    pub(crate) fn flush_at_end(&mut self) -> io::Result<()> {
+        match self.config.flush_every {
+            Some(_) => self.writer.flush(),
+            None => Ok(()),
+        }
+    }
+
+    // Runs up to the end of the ops there are, returns false when the program
+    // was cut short instead.
+    pub(crate) fn run_ops(&mut self) -> Result<bool, HandError> {
+        let len = self.bytecode.ops.len();
+        if self.trace.is_some() {
+            while self.flow_offset < len {
+                if self.step()?.is_none() {
+                    return Ok(false);
+                }
+            }
+            return Ok(true);
+        }
+        // the same as stepping, minus the StepResult nobody would look at
+        while self.flow_offset < len {
+            let op = self.bytecode.ops[self.flow_offset];
+            if !self.execute(op)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Adds an instruction at the given offset in the source after the ops
+    // there are, only for interpreters made by with_lazy_jumps.
+    pub(crate) fn push_unresolved(&mut self, ins: Instruction, offset: usize) {
+        let bytecode = self.bytecode.to_mut();
+        bytecode.ops.push(unresolved_op(ins));
+        bytecode.offsets.push(offset);
+    }
+
+    // Forgets the ops that have run once all of them have, the tape and the
+    // step count stay.
+    pub(crate) fn drop_finished(&mut self) {
+        if self.flow_offset >= self.bytecode.ops.len() {
+            let bytecode = self.bytecode.to_mut();
+            bytecode.ops.clear();
+            bytecode.offsets.clear();
+            self.flow_offset = 0;
+            if let Some(jumps) = &mut self.lazy_jumps {
+                jumps.clear();
+            }
+        }
+    }
+
+    // Runs until the next op to execute has a breakpoint, returning its offset, or
+    // until the program ends. The op at the current offset always runs first so
+    // that calling this again moves past the breakpoint it stopped at.
+    pub fn run_until_breakpoint(&mut self) -> Result<Option<usize>, HandError> {
+        while self.step()?.is_some() {
+            if self.breakpoints.contains(&self.flow_offset)
+                && self.flow_offset < self.bytecode.ops.len()
+            {
+                return Ok(Some(self.flow_offset));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn step(&mut self) -> Result<Option<StepResult>, HandError> {
+        let offset = self.flow_offset;
+        let op = match self.bytecode.ops.get(offset) {
+            Some(&op) => op,
+            None => return Ok(None),
+        };
+        if !self.execute(op)? {
+            return Ok(None);
+        }
+
+        let result = StepResult {
+            offset,
+            op,
+            cursor: self.tape.cursor(),
+            cell: self.tape.get(),
+        };
+        if let Some(trace) = &mut self.trace {
+            trace(&result);
+        }
+        Ok(Some(result))
+    }
+
+    // Runs the op at flow_offset and moves past it. Every op costs the steps of
+    // the instructions it stands for, fused loops as many as their turns, so
+    // max_steps stops a program at the same instruction however it was compiled.
+    // Returns false when the program was cut short instead.
+    fn execute(&mut self, op: Op) -> Result<bool, HandError> {
+        let offset = self.flow_offset;
+        let start = self.bytecode.offsets[offset];
+        // a run max_steps cut short goes on from the instruction it stopped at
+        let at = start + self.loop_step;
+        let budget = self.budget();
+        if budget == 0 {
+            return Err(self.out_of_steps(at));
+        }
+        #[cfg(feature = "std")]
+        if let Some(limit) = self.config.max_duration {
+            if self.until_clock == 0 {
+                self.until_clock = CLOCK_INTERVAL;
+                let started = *self.started.get_or_insert_with(Instant::now);
+                if started.elapsed() > limit {
+                    return Err(HandError::Timeout { limit, offset: at });
+                }
+            }
+            self.until_clock -= 1;
+        }
+        if let Some(profile) = &mut self.profile {
+            profile[offset] += 1;
+        }
+
+        let mut done = true;
+        match op {
+            Op::Move(n) => {
+                let len = self.run_length(n.unsigned_abs(), budget);
+                let moved = self.move_cursor(n.signum() * len as isize);
+                self.charge_run(moved, at, len)?;
+                self.cut_short(len, n.unsigned_abs())?;
+            }
+            Op::Add(n) => {
+                let len = self.run_length(n.unsigned_abs().into(), budget);
+                let added = self.add(at, n.signum() * len as i16);
+                self.charge_run(added, at, len)?;
+                self.cut_short(len, n.unsigned_abs().into())?;
+            }
+            Op::Clear => {
+                let value = u64::from(self.tape.get());
+                // 👇🤛 for each count left, plus the 🤜 or 🤛 unless it stopped at the 👇
+                let cost = 2 * value + u64::from(self.loop_step != 1);
+                if !self.config.signed && cost <= budget {
+                    // counting an unsigned cell down to 0 never overflows in any mode
+                    self.tape.set(0);
+                    self.steps += cost;
+                } else {
+                    // a negative cell can overflow on its way down, or never get to 0, so
+                    // the loop runs as written, one instruction a step
+                    done = self.loop_instruction(op)?;
+                }
+            }
+            Op::JumpIfZero(target) => {
+                self.steps += 1;
+                if self.tape.get() == 0 {
+                    self.flow_offset = self.jump_target(target)?;
+                }
+            }
+            Op::JumpIfNonZero(target) => {
+                self.steps += 1;
+                if self.tape.get() != 0 {
+                    self.flow_offset = self.jump_target(target)?;
+                }
+            }
+            Op::MultiplyLoop(end) => {
+                let count = self.tape.get();
+                // the 🤜, and each turn runs the body and the 🤛
+                let body = self.bytecode.offsets[end] - start;
+                let cost = u64::from(count)
+                    .saturating_mul(body as u64)
+                    .saturating_add(1);
+                if count == 0 {
+                    self.steps += 1;
+                    self.flow_offset = end;
+                } else if cost <= budget
+                    && self.config.overflow == OverflowMode::Wrap
+                    && self.config.on_overflow.is_none()
+                    && !matches!(
+                        self.config.boundary,
+                        BoundaryPolicy::Clamp | BoundaryPolicy::Wrap
+                    )
+                {
+                    // other modes can stop or change course midway, every wrap has to be
+                    // reported one at a time, clamped moves don't come back to the same
+                    // cell and on a ring another position can be the start cell again,
+                    // those run the loop as written, and so does one that would run
+                    // out of steps midway
+                    let multiplied = self.multiply(end, count);
+                    self.charge_run(multiplied, start, cost as usize)?;
+                    self.flow_offset = end;
+                } else {
+                    self.steps += 1;
+                }
+            }
+            Op::SeekZero(step) => done = self.seek_zero(step, budget)?,
+            Op::Print => {
+                self.steps += 1;
+                if !self.print(start, 1)? {
+                    return Ok(false);
+                }
+            }
+            Op::PrintRepeat(count) => {
+                let len = self.run_length(count, budget);
+                // flushing after the right 👊 takes them one at a time
+                let (runs, each) = match self.config.flush_every {
+                    Some(_) => (len, 1),
+                    None => (1, len),
+                };
+                let mut printed = Ok(true);
+                for run in 0..runs {
+                    printed = self.print(at + run, each);
+                    if !matches!(printed, Ok(true)) {
+                        break;
+                    }
+                }
+                if !self.charge_run(printed, at, len)? {
+                    return Ok(false);
+                }
+                self.cut_short(len, count)?;
+            }
+            Op::Input => {
+                self.steps += 1;
+                // a buffered writer could still hold a prompt the user has to see first
+                self.writer.flush()?;
+                let value = match read_byte(&mut self.reader)? {
+                    Some(byte) => Cell::from(byte),
+                    None => match self.config.eof {
+                        EofPolicy::Zero => 0,
+                        EofPolicy::Max => self.config.cell_width.max_value(),
+                        EofPolicy::Unchanged => self.tape.get(),
+                    },
+                };
+                self.tape.set(value);
+            }
+        }
+        // a seek the tape gave up on or a clear that isn't done runs again, like the
+        // loop it replaces would
+        if done {
+            self.loop_step = 0;
+            self.flow_offset += 1;
+        }
+        Ok(true)
+    }
+
+    // Steps left before max_steps, as good as unlimited without it.
+    fn budget(&self) -> u64 {
+        match self.config.max_steps {
+            Some(limit) => limit.saturating_sub(self.steps - self.limit_from),
+            None => u64::MAX,
+        }
+    }
+
+    fn out_of_steps(&self, offset: usize) -> HandError {
+        let limit = self.config.max_steps.unwrap_or(0);
+        HandError::StepLimitExceeded { limit, offset }
+    }
+
+    // Counts the steps of a run of len instructions from start in the program,
+    // or of those up to the one that failed.
+    fn charge_run<T>(
+        &mut self,
+        result: Result<T, HandError>,
+        start: usize,
+        len: usize,
+    ) -> Result<T, HandError> {
+        let ran = match &result {
+            Err(e) => instruction_of(e).map_or(len, |offset| offset - start + 1),
+            Ok(_) => len,
+        };
+        self.steps += ran as u64;
+        result
+    }
+
+    // How many instructions of a run of total the steps left allow, counting
+    // from those that already ran.
+    fn run_length(&self, total: usize, budget: u64) -> usize {
+        ((total - self.loop_step) as u64).min(budget) as usize
+    }
+
+    // Counts len more instructions of a run of total as done, and fails at the
+    // next one when that isn't all of them.
+    fn cut_short(&mut self, len: usize, total: usize) -> Result<(), HandError> {
+        self.loop_step += len;
+        match self.loop_step < total {
+            true => {
+                Err(self.out_of_steps(self.bytecode.offsets[self.flow_offset] + self.loop_step))
+            }
+            false => Ok(()),
+        }
+    }
+
+    // Runs the instruction of the clear or seek loop at flow_offset that is
+    // loop_step in, for a loop that can't run as one op. Returns whether the
+    // loop is done.
+    fn loop_instruction(&mut self, op: Op) -> Result<bool, HandError> {
+        let offset = self.bytecode.offsets[self.flow_offset] + self.loop_step;
+        let (step, moves) = match op {
+            Op::SeekZero(step) => (step.signum(), step.unsigned_abs()),
+            _ => (0, 1),
+        };
+        self.steps += 1;
+        if self.loop_step == 0 || self.loop_step > moves {
+            if self.tape.get() == 0 {
+                return Ok(true);
+            }
+            self.loop_step = 1;
+            return Ok(false);
+        }
+        if step == 0 {
+            self.add(offset, -1)?;
+        } else {
+            let moved = match step {
+                1 => self.tape.move_right(1),
+                _ => self.tape.move_left(1),
+            };
+            moved.map_err(|e| move_error(e, offset))?;
+        }
+        self.loop_step += 1;
+        Ok(false)
+    }
+
+    // Does count 👊 in a row starting at offset in the program, all of them
+    // written with a single call to the writer. Nothing changes the cell in
+    // between, so the output is the same as one at a time. Returns false when
+    // nobody reads the output anymore.
+    fn print(&mut self, offset: usize, count: usize) -> Result<bool, HandError> {
+        let value = self.tape.get();
+        let skip = self.config.nul == NulPolicy::Skip && writes_nul(self.config.output, value);
+        if self.config.strict_ascii && !skip && !prints_ascii(self.config.output, value) {
+            return Err(HandError::NonAsciiOutput { value, offset });
+        }
+        if let Some(limit) = self.config.max_output_bytes {
+            // the 👊 that would go past the limit writes nothing, those before it still do
+            let len = match skip {
+                true => 0,
+                false => write_cell(&mut io::sink(), &self.config, value)? as u64,
+            };
+            let room = limit - self.written;
+            if len * count as u64 > room {
+                let fits = (room / len) as usize;
+                if fits > 0 && !self.print(offset, fits)? {
+                    return Ok(false);
+                }
+                let offset = offset + fits;
+                return Err(HandError::OutputLimitExceeded { limit, offset });
+            }
+        }
+        let printed = match (skip, count) {
+            (true, _) => Ok(0),
+            (false, 1) => write_cell(&mut self.writer, &self.config, value),
+            (false, _) => {
+                let mut once = Vec::new();
+                let len = write_cell(&mut once, &self.config, value)?;
+                self.writer.write_all(&once.repeat(count)).map(|()| len)
+            }
+        };
+        let before = self.prints;
+        self.prints += count as u64;
+        let printed = match self.config.flush_every {
+            // 0 flushes after every 👊 like 1 does instead of dividing by it
+            Some(n) if self.prints / n.max(1) > before / n.max(1) => {
+                printed.and_then(|len| self.writer.flush().map(|()| len))
+            }
+            _ => printed,
+        };
+        let len = match printed {
+            // nobody is reading anymore (e.g. piped into `head`), so the program is done
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {
+                self.flow_offset = self.bytecode.ops.len();
+                return Ok(false);
+            }
+            result => result?,
+        };
+        self.written += (len * count) as u64;
+        if let Some(hook) = &self.config.on_output {
+            for i in 0..count {
+                hook.call(&OutputEvent {
+                    value,
+                    char: printed_char(self.config.output, value),
+                    cursor: self.tape.cursor(),
+                    offset: offset + i,
+                    len,
+                });
+            }
+        }
+        Ok(true)
+    }
+
+    // Adds n to the cell as a run of single 👆 or 👇, the first of them at
+    // offset in the program.
+    fn add(&mut self, offset: usize, n: i16) -> Result<(), HandError> {
+        let width = self.config.cell_width;
+        let max = width.max_value();
+        let v = self.tape.get();
+        let (value, low, high) = if self.config.signed {
+            let half = i64::from(max / 2);
+            (width.to_signed(v), -half - 1, half)
+        } else {
+            (i64::from(v), 0, i64::from(max))
+        };
+        let room = if n < 0 { value - low } else { high - value };
+        let amount = i64::from(n.unsigned_abs());
+        let value = match self.config.overflow {
+            _ if amount <= room => v.wrapping_add(n as Cell) & max,
+            OverflowMode::Wrap => {
+                let wrapped = v.wrapping_add(n as Cell) & max;
+                if let Some(hook) = &self.config.on_overflow {
+                    hook.call(&OverflowEvent {
+                        value: wrapped,
+                        carry: (value + i64::from(n) - low).div_euclid(high - low + 1),
+                        cursor: self.tape.cursor(),
+                        offset: offset + room as usize,
+                    });
+                }
+                wrapped
+            }
+            OverflowMode::Saturate if n < 0 => low as Cell & max,
+            OverflowMode::Saturate => high as Cell & max,
+            OverflowMode::Error => {
+                // the op stands for a run of single steps, the one that overflows is room steps in
+                let offset = offset + room as usize;
+                return Err(HandError::CellOverflow { offset });
+            }
+        };
+        self.tape.set(value);
+        Ok(())
+    }
+
+    // Runs the body of the multiply loop ending at end once, with every add
+    // other than those to the starting cell done count times.
+    fn multiply(&mut self, end: usize, count: Cell) -> Result<(), HandError> {
+        let max = self.config.cell_width.max_value();
+        let mut position = 0;
+        for index in self.flow_offset + 1..end {
+            // errors point at the op that failed
+            self.flow_offset = index;
+            match self.bytecode.ops[index] {
+                Op::Move(n) => {
+                    self.move_cursor(n)?;
+                    position += n;
+                }
+                Op::Add(n) if position != 0 => {
+                    let amount = (n as Cell).wrapping_mul(count);
+                    self.tape.set(self.tape.get().wrapping_add(amount) & max);
+                }
+                _ => {}
+            }
+        }
+        self.tape.set(0);
+        Ok(())
+    }
+
+    fn jump_target(&mut self, target: usize) -> Result<usize, HandError> {
+        let Some(jumps) = &mut self.lazy_jumps else {
+            return Ok(target);
+        };
+        let from = self.flow_offset;
+        if let Some(&target) = jumps.get(&from) {
+            return Ok(target);
+        }
+        let ops = &self.bytecode.ops;
+        let forward = matches!(ops[from], Op::JumpIfZero(_));
+        let mut open = Vec::new();
+        let mut index = from;
+        loop {
+            // stepping past either end means the loop has no other end
+            index = match forward {
+                true if index + 1 < ops.len() => index + 1,
+                false if index > 0 => index - 1,
+                true => {
+                    let offset = self.bytecode.offsets[from];
+                    return Err(HandError::UnmatchedLoopStart { offset });
+                }
+                false => {
+                    let offset = self.bytecode.offsets[from];
+                    return Err(HandError::UnmatchedLoopEnd { offset });
+                }
+            };
+            let opens = match ops[index] {
+                Op::JumpIfZero(_) => forward,
+                Op::JumpIfNonZero(_) => !forward,
+                _ => continue,
+            };
+            if opens {
+                open.push(index);
+                continue;
+            }
+            let start = open.pop().unwrap_or(from);
+            jumps.insert(start, index);
+            jumps.insert(index, start);
+            if start == from {
+                return Ok(index);
+            }
+        }
+    }
+
+    // Seeks as far as the steps left allow, the turn that doesn't fit anymore
+    // runs one instruction at a time. Returns whether the seek is done.
+    fn seek_zero(&mut self, step: isize, budget: u64) -> Result<bool, HandError> {
+        let turn = step.unsigned_abs() as u64 + 1;
+        let entry = u64::from(self.loop_step == 0);
+        if self.loop_step > 1 || budget < entry + turn {
+            return self.loop_instruction(Op::SeekZero(step));
+        }
+        self.steps += entry;
+        self.loop_step = 1;
+        if self.tape.get() == 0 {
+            return Ok(true);
+        }
+        let cursor = self.tape.cursor();
+        let max_moves = ((budget - entry) / turn).try_into().unwrap_or(usize::MAX);
+        match self.tape.seek_zero(step, max_moves) {
+            Ok(moves) => {
+                self.steps += moves as u64 * turn;
+                Ok(self.tape.get() == 0)
+            }
+            Err(e) => {
+                // only tapes that don't wrap fail, so the distance tells how many
+                // turns got through before the one that failed
+                let turns = (self.tape.cursor() - cursor).unsigned_abs() / step.unsigned_abs();
+                let e = move_error(e, self.bytecode.offsets[self.flow_offset] + 1);
+                let failed = instruction_of(&e)
+                    .map_or(0, |offset| offset - self.bytecode.offsets[self.flow_offset]);
+                self.steps += turns as u64 * turn + failed as u64;
+                Err(e)
+            }
+        }
+    }
+
+    fn move_cursor(&mut self, n: isize) -> Result<(), HandError> {
+        let moved = if n < 0 {
+            self.tape.move_left(n.unsigned_abs())
+        } else {
+            self.tape.move_right(n as usize)
+        };
+        // the op stands for a run of single moves, the one that fails is moved steps in
+        let offset = self.bytecode.offsets[self.flow_offset] + self.loop_step;
+        moved.map_err(|e| move_error(e, offset))
+    }
+}
+
+// The instruction a runtime error points at, for the errors an op can fail with
+// partway through.
+fn instruction_of(e: &HandError) -> Option<usize> {
+    match *e {
+        HandError::PointerUnderflow { offset }
+        | HandError::TapeOverflow { offset, .. }
+        | HandError::CellOverflow { offset }
+        | HandError::NonAsciiOutput { offset, .. }
+        | HandError::OutputLimitExceeded { offset, .. } => Some(offset),
+        _ => None,
+    }
+}
+
+fn move_to(tape: &mut dyn Tape, position: isize) -> Result<(), TapeError> {
+    let n = position - tape.cursor();
+    if n < 0 {
+        tape.move_left(n.unsigned_abs())
+    } else {
+        tape.move_right(n as usize)
+    }
+}
+
+// The error of a failed run of moves starting at the instruction at offset.
+pub(crate) fn move_error(e: TapeError, offset: usize) -> HandError {
+    match e {
+        TapeError::Underflow { moved } => HandError::PointerUnderflow {
+            offset: offset + moved,
+        },
+        TapeError::Overflow { limit, moved } => HandError::TapeOverflow {
+            limit,
+            offset: offset + moved,
+        },
+        TapeError::OutOfRange { .. } => unreachable!("moving the pointer never gives OutOfRange"),
+    }
+}
+
+// Whether 👊 writes printable ASCII, a newline or a tab for the value. Numbers
+// always do.
+fn prints_ascii(mode: OutputMode, value: Cell) -> bool {
+    let value = match mode {
+        OutputMode::Bytes => value & 0xFF,
+        OutputMode::Unicode => value,
+        OutputMode::Decimal => return true,
+    };
+    matches!(value, 0x20..=0x7E) || value == Cell::from(b'\n') || value == Cell::from(b'\t')
+}
+
+fn writes_nul(mode: OutputMode, value: Cell) -> bool {
+    match mode {
+        OutputMode::Bytes => value & 0xFF == 0,
+        OutputMode::Unicode => value == 0,
+        OutputMode::Decimal => false,
+    }
+}
+
+// Returns how many bytes were written.
+fn write_cell(
+    writer: &mut impl Write,
+    config: &InterpreterConfig,
+    value: Cell,
+) -> io::Result<usize> {
+    let newline = match config.output {
+        OutputMode::Bytes => value as u8 == b'\n',
+        OutputMode::Unicode => value == Cell::from(b'\n'),
+        OutputMode::Decimal => false,
+    };
+    if newline && config.line_ending.is_crlf() {
+        writer.write_all(b"\r\n")?;
+        return Ok(2);
+    }
+    match config.output {
+        OutputMode::Bytes => writer.write_all(&[value as u8]).map(|_| 1),
+        OutputMode::Unicode => {
+            let c = char::from_u32(value).unwrap_or(char::REPLACEMENT_CHARACTER);
+            writer.write_all(c.encode_utf8(&mut [0u8; 4]).as_bytes())?;
+            Ok(c.len_utf8())
+        }
+        OutputMode::Decimal => {
+            let number = if config.signed {
+                config.cell_width.to_signed(value)
+            } else {
+                i64::from(value)
+            };
+            let text = format!("{} ", number);
+            writer.write_all(text.as_bytes())?;
+            Ok(text.len())
+        }
+    }
+}
+
+fn printed_char(mode: OutputMode, value: Cell) -> Option<char> {
+    let c = match mode {
+        // a lone byte past ASCII is only part of a character
+        OutputMode::Bytes => Some(value as u8).filter(u8::is_ascii).map(char::from),
+        OutputMode::Unicode => char::from_u32(value),
+        OutputMode::Decimal => None,
+    };
+    c.filter(|c| !c.is_control())
+}
+
+pub(crate) fn read_byte(reader: &mut impl Read) -> io::Result<Option<u8>> {
+    let mut byte = [0u8];
+    loop {
+        match reader.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[test]
+pub fn test_step() -> Result<(), HandError> {
+    let (_, instructions) = crate::parse_hand_code("👆👆").unwrap();
+    let mut interpreter = Interpreter::new(&instructions, io::empty(), io::sink())?;
+    assert_eq!(*interpreter.buffer(), [0]);
+
+    let first = interpreter.step()?.unwrap();
+    assert_eq!(first.offset, 0);
+    assert_eq!(first.op, Op::Add(1));
+    assert_eq!(first.cell, 1);
+    assert_eq!(*interpreter.buffer(), [1]);
+
+    let second = interpreter.step()?.unwrap();
+    assert_eq!(second.offset, 1);
+    assert_eq!(second.cell, 2);
+    assert_eq!(*interpreter.buffer(), [2]);
+
+    assert_eq!(interpreter.step()?, None);
+    assert_eq!(interpreter.steps(), 2);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_step_through_loop() -> Result<(), HandError> {
+    let (_, instructions) = crate::parse_hand_code("👆👆🤜👇🤛👊").unwrap();
+    let mut output = Vec::new();
+    let mut interpreter = Interpreter::new(&instructions, io::empty(), &mut output)?;
+    let mut offsets = Vec::new();
+
+    while let Some(step) = interpreter.step()? {
+        offsets.push(step.offset);
+    }
+
+    assert_eq!(offsets, vec![0, 1, 2, 3, 4, 3, 4, 5]);
+    assert_eq!(output, vec![0]);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_breakpoint_inside_loop() -> Result<(), HandError> {
+    let (_, instructions) = crate::parse_hand_code("👆👆👆🤜👉👆👈👇🤛👉👊").unwrap();
+    let mut output = Vec::new();
+    let mut interpreter = Interpreter::new(&instructions, io::empty(), &mut output)?;
+    interpreter.set_breakpoint(6);
+
+    assert_eq!(interpreter.run_until_breakpoint()?, Some(6));
+    assert_eq!(interpreter.cursor(), 1);
+    assert_eq!(*interpreter.buffer(), [3, 1]);
+
+    assert_eq!(interpreter.run_until_breakpoint()?, Some(6));
+    assert_eq!(*interpreter.buffer(), [2, 2]);
+
+    interpreter.clear_breakpoint(6);
+    assert_eq!(interpreter.run_until_breakpoint()?, None);
+    assert_eq!(*interpreter.buffer(), [0, 3]);
+    assert_eq!(output, vec![3]);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_memory_dump() -> Result<(), HandError> {
+    let code =
+        "👆👆👆👆👆👆🤜👇👉👆👆👆👆👆👆👆👆👆👆👆👉👆👆👆👆👆👆👆👆👆👆👆👈👈🤛👉👉👇👇👇👇👇👇";
+    let (_, instructions) = crate::parse_hand_code(code).unwrap();
+    let mut interpreter = Interpreter::new(&instructions, io::empty(), io::sink())?;
+    interpreter.set_breakpoint(instructions.len() - 6);
+
+    interpreter.run_until_breakpoint()?;
+    let memory = interpreter.memory();
+
+    assert_eq!(*memory.cells, [0, 66, 66]);
+    assert_eq!(memory.cursor, 2);
+    assert_eq!(
+        memory.hex_dump(),
+        "00000000: 0042 42                                  .BB\n"
+    );
+
+    interpreter.run()?;
+    assert_eq!(*interpreter.memory().cells, [0, 66, 60]);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_trace() -> Result<(), HandError> {
+    let (_, instructions) = crate::parse_hand_code("👆👆🤜👉👆👈👇🤛").unwrap();
+    let mut trace = Vec::new();
+    let mut interpreter = Interpreter::new(&instructions, io::empty(), io::sink())?;
+    interpreter.set_trace(|step| trace.push((step.offset, step.op, step.cursor, step.cell)));
+
+    interpreter.run()?;
+    drop(interpreter);
+
+    let loop_body = |cell| {
+        [
+            (3, Op::Move(1), 1, 2 - cell),
+            (4, Op::Add(1), 1, 3 - cell),
+            (5, Op::Move(-1), 0, cell),
+            (6, Op::Add(-1), 0, cell - 1),
+        ]
+    };
+    let mut expected = vec![(0, Op::Add(1), 0, 1), (1, Op::Add(1), 0, 2)];
+    expected.push((2, Op::JumpIfZero(7), 0, 2));
+    expected.extend(loop_body(2));
+    expected.push((7, Op::JumpIfNonZero(2), 0, 1));
+    expected.extend(loop_body(1));
+    expected.push((7, Op::JumpIfNonZero(2), 0, 0));
+    assert_eq!(trace, expected);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_profile() -> Result<(), HandError> {
+    let (_, instructions) = crate::parse_hand_code("👆👆👆👆🤜👉👆👆👈👇🤛👉👊").unwrap();
+    let mut interpreter = Interpreter::new(&instructions, io::empty(), io::sink())?;
+    assert_eq!(interpreter.profile(), None);
+    interpreter.enable_profiling();
+
+    interpreter.run()?;
+
+    let profile = interpreter.profile().unwrap();
+    assert_eq!(profile, &[1, 1, 1, 1, 1, 4, 4, 4, 4, 4, 4, 1, 1]);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_uncovered() -> Result<(), HandError> {
+    // the cell is 0 at the first loop, so its body can never run
+    let (_, instructions) = crate::parse_hand_code("👊🤜👆👆👊🤛👆🤜👇🤛👊").unwrap();
+    let mut interpreter = Interpreter::new(&instructions, io::empty(), io::sink())?;
+    assert_eq!(interpreter.uncovered(), None);
+    interpreter.enable_profiling();
+
+    interpreter.run()?;
+
+    let uncovered = interpreter.uncovered().unwrap();
+    assert_eq!(uncovered.into_iter().collect::<Vec<_>>(), [2, 3, 4, 5]);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_broken_pipe_ends_program() -> Result<(), HandError> {
+    struct ClosedPipe;
+
+    impl Write for ClosedPipe {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // prints forever unless the write fails
+    let (_, instructions) = crate::parse_hand_code("👆🤜👊🤛").unwrap();
+    let mut interpreter = Interpreter::new(&instructions, io::empty(), ClosedPipe)?;
+
+    interpreter.run()?;
+    assert_eq!(interpreter.steps(), 3);
+    assert_eq!(interpreter.step()?, None);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_partial_writes() -> Result<(), HandError> {
+    struct OneByteAtATime(Vec<u8>);
+
+    impl Write for OneByteAtATime {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.extend_from_slice(&buf[..buf.len().min(1)]);
+            Ok(buf.len().min(1))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // 233 is é, written as two bytes in Unicode mode
+    let code = format!("{}👊", "👇".repeat(23));
+    let (_, instructions) = crate::parse_hand_code(&code).unwrap();
+    let config = InterpreterConfig {
+        output: OutputMode::Unicode,
+        ..InterpreterConfig::default()
+    };
+    let mut writer = OneByteAtATime(Vec::new());
+
+    Interpreter::with_config(&config, translate(&instructions)?, io::empty(), &mut writer).run()?;
+
+    assert_eq!(String::from_utf8(writer.0).unwrap(), "é");
+
+    Ok(())
+}
+
+#[test]
+pub fn test_seek_over_blank_cells_that_are_not_zero() -> Result<(), HandError> {
+    use crate::TapeKind;
+
+    // no cell is ever 0, so both seeks go on until the step limit
+    for code in ["👆🤜👉🤛", "👆🤜👉👉🤛"] {
+        let instructions = crate::parse_program(code)?;
+        let bytecode = crate::compile(&instructions)?;
+        assert!(matches!(bytecode.ops[1], Op::SeekZero(_)));
+        for (tape, boundary) in [
+            (TapeKind::Contiguous, BoundaryPolicy::Error),
+            (TapeKind::Sparse, BoundaryPolicy::Error),
+            (TapeKind::Contiguous, BoundaryPolicy::Wrap),
+            (TapeKind::Contiguous, BoundaryPolicy::Clamp),
+        ] {
+            let config = InterpreterConfig {
+                tape,
+                boundary,
+                initial_cell_value: 1,
+                initial_tape_size: 4,
+                max_steps: Some(1000),
+                ..InterpreterConfig::default()
+            };
+            let result = crate::run_bytecode(&config, io::empty(), io::sink(), &bytecode);
+            assert!(matches!(
+                result,
+                Err(HandError::StepLimitExceeded { limit: 1000, .. })
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn test_clear_signed_negative_cell() -> Result<(), HandError> {
+    use std::sync::{Arc, Mutex};
+
+    // -3, then 🤜👇🤛 counts it down past -128
+    let instructions = crate::parse_program("👇👇👇🤜👇🤛👊")?;
+    let run = |config: &InterpreterConfig, bytecode: Bytecode| {
+        let mut output = Vec::new();
+        let mut interpreter = Interpreter::with_config(config, bytecode, io::empty(), &mut output);
+        let result = interpreter.run();
+        let buffer = interpreter.buffer().into_owned();
+        drop(interpreter);
+        (result, buffer, output)
+    };
+    let both = |config: &InterpreterConfig| {
+        let compiled = crate::compile(&instructions).unwrap();
+        assert!(compiled.ops.contains(&Op::Clear));
+        (
+            run(config, translate(&instructions).unwrap()),
+            run(config, compiled),
+        )
+    };
+    let signed = |overflow| InterpreterConfig {
+        signed: true,
+        overflow,
+        max_steps: Some(10_000),
+        ..InterpreterConfig::default()
+    };
+
+    let (translated, compiled) = both(&signed(OverflowMode::Error));
+    for (result, buffer, output) in [translated, compiled] {
+        assert!(matches!(result, Err(HandError::CellOverflow { offset: 4 })));
+        assert_eq!((buffer, output), (vec![0x80], vec![]));
+    }
+
+    let (translated, compiled) = both(&signed(OverflowMode::Saturate));
+    for (result, buffer, _) in [translated, compiled] {
+        assert!(matches!(result, Err(HandError::StepLimitExceeded { .. })));
+        assert_eq!(buffer, [0x80]);
+    }
+
+    let origins = Arc::new(Mutex::new(Vec::new()));
+    let collected = Arc::clone(&origins);
+    let hooked = InterpreterConfig {
+        on_overflow: Some(crate::OverflowHook::new(move |event| {
+            collected.lock().unwrap().push((event.offset, event.value))
+        })),
+        ..signed(OverflowMode::Wrap)
+    };
+    let (translated, compiled) = both(&hooked);
+    for (result, buffer, output) in [translated, compiled] {
+        assert!(result.is_ok());
+        assert_eq!((buffer, output), (vec![0], vec![0]));
+    }
+    assert_eq!(*origins.lock().unwrap(), [(4, 0x7f), (4, 0x7f)]);
+
+    // plain wrapping gets to 0 either way
+    let (translated, compiled) = both(&signed(OverflowMode::Wrap));
+    assert_eq!(translated.1, compiled.1);
+    assert!(compiled.0.is_ok());
+
+    // an unsigned cell is cleared in one go, the loop would take 2^32 - 1 turns
+    let unsigned = InterpreterConfig {
+        cell_width: crate::CellWidth::U32,
+        overflow: OverflowMode::Saturate,
+        initial_cell_value: u32::MAX,
+        ..InterpreterConfig::default()
+    };
+    let (result, buffer, _) = run(&unsigned, crate::compile(&instructions[3..])?);
+    assert!(result.is_ok());
+    assert_eq!(buffer, [0]);
+    Ok(())
+}
+
+#[test]
+pub fn test_signed_cells() -> Result<(), HandError> {
+    let config = InterpreterConfig {
+        signed: true,
+        ..InterpreterConfig::default()
+    };
+    let (_, instructions) = crate::parse_hand_code("👇👉👆👆🤜👇🤛").unwrap();
+    let mut interpreter =
+        Interpreter::with_config(&config, translate(&instructions)?, io::empty(), io::sink());
+
+    interpreter.run()?;
+
+    assert_eq!(*interpreter.buffer(), [255, 0]);
+    assert_eq!(interpreter.memory().to_string(), "-1 [0]");
+
+    let saturating = InterpreterConfig {
+        overflow: OverflowMode::Saturate,
+        ..config.clone()
+    };
+    let code = format!("{}👉{}", "👆".repeat(200), "👇".repeat(200));
+    let (_, instructions) = crate::parse_hand_code(&code).unwrap();
+    let mut interpreter = Interpreter::with_config(
+        &saturating,
+        translate(&instructions)?,
+        io::empty(),
+        io::sink(),
+    );
+    interpreter.run()?;
+    assert_eq!(interpreter.memory().to_string(), "127 [-128]");
+
+    let checked = InterpreterConfig {
+        overflow: OverflowMode::Error,
+        ..config
+    };
+    let code = "👆".repeat(128);
+    let (_, instructions) = crate::parse_hand_code(&code).unwrap();
+    let result = crate::run_hand_ast_with_config(&checked, io::empty(), io::sink(), &instructions);
+    assert!(matches!(
+        result,
+        Err(HandError::CellOverflow { offset: 127 })
+    ));
+
+    Ok(())
+}
+
+#[test]
+pub fn test_resume_after_step_limit() -> Result<(), HandError> {
+    let instructions = crate::parse_program(include_str!("../../test2.hand"))?;
+    let config = InterpreterConfig {
+        max_steps: Some(10),
+        ..InterpreterConfig::default()
+    };
+    let mut expected = Vec::new();
+    crate::run_hand_ast(io::empty(), &mut expected, &instructions)?;
+
+    let mut output = Vec::new();
+    let bytecode = crate::compile(&instructions)?;
+    let mut interpreter = Interpreter::with_config(&config, bytecode, io::empty(), &mut output);
+    let mut pauses = 0;
+    while let Err(e) = interpreter.run() {
+        assert!(matches!(e, HandError::StepLimitExceeded { limit: 10, .. }));
+        assert_eq!(interpreter.steps(), (pauses + 1) * 10);
+        pauses += 1;
+    }
+    drop(interpreter);
+
+    assert!(pauses > 1);
+    assert_eq!(output, expected);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_step_limit_counts_instructions() -> Result<(), HandError> {
+    // a run, a clear, a multiply loop, a seek over three cells and a repeated 👊
+    let code = "👆👆👆👆🤜👇🤛👆👆👆🤜👇👉👆👆👈🤛👉👉👉👆🤜👈🤛👉👆👊👊👊";
+    let instructions = crate::parse_program(code)?;
+    let translated = crate::translate(&instructions)?;
+    let compiled = crate::compile(&instructions)?;
+    assert!(compiled.ops.len() < translated.ops.len());
+    let run = |bytecode: &Bytecode, max_steps| {
+        let config = InterpreterConfig {
+            max_steps,
+            ..InterpreterConfig::default()
+        };
+        let mut output = Vec::new();
+        let mut interpreter = Interpreter::with_config(&config, bytecode, io::empty(), &mut output);
+        // errors don't compare, their messages show the variant and the offset
+        let result = interpreter.run().map_err(|e| e.to_string());
+        let state = interpreter.into_state();
+        (result, state.buffer(), state.cursor, state.steps, output)
+    };
+
+    let (result, _, _, steps, _) = run(&translated, None);
+    assert!(result.is_ok());
+    assert_eq!(run(&compiled, None).3, steps);
+    // every limit stops both at the same instruction with the same tape
+    for limit in 1..=steps {
+        let slow = run(&translated, Some(limit));
+        assert_eq!(run(&compiled, Some(limit)), slow);
+        assert_eq!(slow.0.is_err(), limit < steps);
+    }
+
+    // resuming a run cut short partway goes on from the same instruction
+    let config = InterpreterConfig {
+        max_steps: Some(3),
+        ..InterpreterConfig::default()
+    };
+    let mut output = Vec::new();
+    let mut interpreter = Interpreter::with_config(&config, &compiled, io::empty(), &mut output);
+    while interpreter.run().is_err() {}
+    assert_eq!(interpreter.steps(), steps);
+    drop(interpreter);
+    assert_eq!(output, run(&translated, None).4);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_crlf_line_ending() -> Result<(), HandError> {
+    let code = include_str!("../../test2.hand").trim();
+    let (_, instructions) = crate::parse_hand_code(code).unwrap();
+    let run = |output, line_ending| -> Result<Vec<u8>, HandError> {
+        let config = InterpreterConfig {
+            output,
+            line_ending,
+            ..InterpreterConfig::default()
+        };
+        let mut writer = Vec::new();
+        Interpreter::with_config(&config, translate(&instructions)?, io::empty(), &mut writer)
+            .run()?;
+        Ok(writer)
+    };
+
+    assert_eq!(
+        run(OutputMode::Bytes, crate::LineEnding::Unchanged)?,
+        b"Hello World!\n"
+    );
+    assert_eq!(
+        run(OutputMode::Bytes, crate::LineEnding::CrLf)?,
+        b"Hello World!\r\n"
+    );
+    assert_eq!(
+        run(OutputMode::Unicode, crate::LineEnding::CrLf)?,
+        b"Hello World!\r\n"
+    );
+    let native: &[u8] = if cfg!(windows) { b"\r\n" } else { b"\n" };
+    assert!(run(OutputMode::Bytes, crate::LineEnding::Native)?.ends_with(native));
+
+    Ok(())
+}
+
+#[test]
+pub fn test_output_events() -> Result<(), HandError> {
+    use crate::{OutputEvent, OutputHook};
+    use std::sync::{Arc, Mutex};
+
+    let code = include_str!("../../test1.hand").trim();
+    let (_, instructions) = crate::parse_hand_code(code).unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let collected = Arc::clone(&events);
+    let config = InterpreterConfig {
+        on_output: Some(OutputHook::new(move |event| {
+            collected.lock().unwrap().push(*event)
+        })),
+        ..InterpreterConfig::default()
+    };
+    let mut output = Vec::new();
+
+    Interpreter::with_config(
+        &config,
+        crate::compile(&instructions)?,
+        io::empty(),
+        &mut output,
+    )
+    .run()?;
+
+    assert_eq!(output, b"Hello");
+    let prints = instructions
+        .iter()
+        .enumerate()
+        .filter(|&(_, &ins)| ins == Instruction::Print)
+        .map(|(offset, _)| offset);
+    let expected: Vec<_> = "Hello"
+        .chars()
+        .zip([1, 2, 2, 2, 2])
+        .zip(prints)
+        .map(|((c, cursor), offset)| OutputEvent {
+            value: c as Cell,
+            char: Some(c),
+            cursor,
+            offset,
+            len: 1,
+        })
+        .collect();
+    assert_eq!(*events.lock().unwrap(), expected);
+
+    assert_eq!(printed_char(OutputMode::Bytes, 10), None);
+    assert_eq!(printed_char(OutputMode::Bytes, 233), None);
+    assert_eq!(printed_char(OutputMode::Unicode, 233), Some('é'));
+
+    Ok(())
+}
+
+#[test]
+pub fn test_output_positions() -> Result<(), HandError> {
+    use crate::OutputHook;
+    use std::sync::{Arc, Mutex};
+
+    // which 👊 each byte of output came from
+    let collect = |config: InterpreterConfig| {
+        let origins = Arc::new(Mutex::new(Vec::new()));
+        let collected = Arc::clone(&origins);
+        let config = InterpreterConfig {
+            on_output: Some(OutputHook::new(move |event: &OutputEvent| {
+                let mut origins = collected.lock().unwrap();
+                origins.extend(core::iter::repeat_n(event.offset, event.len));
+            })),
+            ..config
+        };
+        (config, origins)
+    };
+
+    let code = include_str!("../../test1.hand").trim();
+    let (_, instructions) = crate::parse_hand_code(code).unwrap();
+    let (config, origins) = collect(InterpreterConfig::default());
+    let mut output = Vec::new();
+    Interpreter::with_config(&config, translate(&instructions)?, io::empty(), &mut output).run()?;
+    let prints: Vec<_> = instructions
+        .iter()
+        .enumerate()
+        .filter(|&(_, &ins)| ins == Instruction::Print)
+        .map(|(offset, _)| offset)
+        .collect();
+    assert_eq!(output, b"Hello");
+    assert_eq!(prints.len(), 5);
+    assert_eq!(*origins.lock().unwrap(), prints);
+
+    // a 👊 can write more than one byte
+    let (_, instructions) = crate::parse_hand_code("✋👊✋👊").unwrap();
+    let (config, origins) = collect(InterpreterConfig {
+        output: OutputMode::Unicode,
+        line_ending: crate::LineEnding::CrLf,
+        ..InterpreterConfig::default()
+    });
+    let mut output = Vec::new();
+    let input: &[u8] = &[233, b'\n'];
+    Interpreter::with_config(&config, translate(&instructions)?, input, &mut output).run()?;
+    assert_eq!(output, "é\r\n".as_bytes());
+    assert_eq!(*origins.lock().unwrap(), [1, 1, 3, 3]);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_timeout() -> Result<(), HandError> {
+    use std::time::{Duration, Instant};
+
+    let (_, instructions) = crate::parse_hand_code("👆🤜🤛").unwrap();
+    let limit = Duration::from_millis(5);
+    let config = InterpreterConfig {
+        max_duration: Some(limit),
+        ..InterpreterConfig::default()
+    };
+    let mut interpreter = Interpreter::with_config(
+        &config,
+        crate::compile(&instructions)?,
+        io::empty(),
+        io::sink(),
+    );
+
+    let started = Instant::now();
+    let error = interpreter.run().unwrap_err();
+
+    assert!(started.elapsed() >= limit);
+    assert!(matches!(error, HandError::Timeout { offset: 1 | 2, .. }));
+    assert!(error
+        .to_string()
+        .starts_with("program did not finish within 5ms"));
+    let steps = interpreter.steps();
+    assert!(interpreter.run().is_err());
+    assert!(interpreter.steps() > steps);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_peek_and_poke() -> Result<(), HandError> {
+    let (_, instructions) = crate::parse_hand_code("👆👊👉👊👈👊").unwrap();
+
+    for tape in [crate::TapeKind::Contiguous, crate::TapeKind::Sparse] {
+        let config = InterpreterConfig {
+            tape,
+            ..InterpreterConfig::default()
+        };
+        let mut output = Vec::new();
+        let mut interpreter =
+            Interpreter::with_config(&config, translate(&instructions)?, io::empty(), &mut output);
+
+        interpreter.step()?;
+        interpreter.step()?;
+        assert_eq!(interpreter.peek(0), Ok(1));
+        assert_eq!(interpreter.peek(1), Ok(0));
+        interpreter.poke(1, 0x141).unwrap();
+        interpreter.poke(0, 7).unwrap();
+        assert_eq!(interpreter.peek(1), Ok(0x41));
+        assert_eq!(interpreter.cursor(), 0);
+        assert_eq!(
+            interpreter.poke(-1, 1),
+            Err(TapeError::Underflow { moved: 0 })
+        );
+        interpreter.run()?;
+
+        assert_eq!(*interpreter.buffer(), [7, 0x41]);
+        drop(interpreter);
+        assert_eq!(output, [1, 0x41, 7]);
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn test_absolute_addressing() -> Result<(), HandError> {
+    let (_, instructions) = crate::parse_hand_code("👉👆👆👉").unwrap();
+
+    for tape in [crate::TapeKind::Contiguous, crate::TapeKind::Sparse] {
+        let config = InterpreterConfig {
+            tape,
+            ..InterpreterConfig::default()
+        };
+        let mut interpreter =
+            Interpreter::with_config(&config, translate(&instructions)?, io::empty(), io::sink());
+        interpreter.run()?;
+        assert_eq!(*interpreter.buffer(), [0, 2, 0]);
+
+        // only the cells there are
+        assert_eq!(interpreter.read_at(1, IndexPolicy::Error), Ok(2));
+        assert_eq!(
+            interpreter.read_at(3, IndexPolicy::Error),
+            Err(TapeError::OutOfRange { index: 3 })
+        );
+        assert_eq!(
+            interpreter.write_at(-1, 1, IndexPolicy::Error),
+            Err(TapeError::OutOfRange { index: -1 })
+        );
+        interpreter.write_at(0, 0x105, IndexPolicy::Error).unwrap();
+        assert_eq!(*interpreter.buffer(), [5, 2, 0]);
+
+        // growing stops where moving the pointer would
+        interpreter.write_at(5, 9, IndexPolicy::Grow).unwrap();
+        assert_eq!(*interpreter.buffer(), [5, 2, 0, 0, 0, 9]);
+        assert!(matches!(
+            interpreter.read_at(-1, IndexPolicy::Grow),
+            Err(TapeError::Underflow { .. })
+        ));
+
+        // around the six cells
+        assert_eq!(interpreter.read_at(7, IndexPolicy::Wrap), Ok(2));
+        assert_eq!(interpreter.read_at(-1, IndexPolicy::Wrap), Ok(9));
+        interpreter.write_at(-6, 3, IndexPolicy::Wrap).unwrap();
+        assert_eq!(*interpreter.buffer(), [3, 2, 0, 0, 0, 9]);
+        assert_eq!(interpreter.cursor(), 2);
+    }
+
+    // a tape that grows left counts from its lowest cell
+    let config = InterpreterConfig {
+        boundary: crate::BoundaryPolicy::Grow,
+        ..InterpreterConfig::default()
+    };
+    let mut interpreter =
+        Interpreter::with_config(&config, translate(&instructions)?, io::empty(), io::sink());
+    interpreter.run()?;
+    interpreter.write_at(-2, 4, IndexPolicy::Grow).unwrap();
+    assert_eq!(*interpreter.buffer(), [4, 0, 0, 2, 0]);
+    assert_eq!(interpreter.read_at(3, IndexPolicy::Wrap), Ok(4));
+    assert_eq!(interpreter.read_at(-2, IndexPolicy::Error), Ok(4));
+    assert_eq!(
+        interpreter.read_at(-3, IndexPolicy::Error),
+        Err(TapeError::OutOfRange { index: -3 })
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn test_rewind_and_reset() -> Result<(), HandError> {
+    let (_, instructions) = crate::parse_hand_code("👆👉👆👆👊").unwrap();
+    let mut output = Vec::new();
+    let mut interpreter = Interpreter::new(&instructions, io::empty(), &mut output)?;
+
+    interpreter.run()?;
+    interpreter.run()?;
+    assert_eq!(interpreter.steps(), 5);
+    interpreter.rewind();
+    interpreter.run()?;
+    assert_eq!(*interpreter.buffer(), [1, 3, 2]);
+    assert_eq!(interpreter.cursor(), 2);
+    assert_eq!(interpreter.steps(), 10);
+
+    interpreter.reset();
+    assert_eq!(*interpreter.buffer(), [0]);
+    assert_eq!((interpreter.cursor(), interpreter.flow_offset()), (0, 0));
+    interpreter.run()?;
+    assert_eq!(*interpreter.buffer(), [1, 2]);
+    assert_eq!(interpreter.steps(), 5);
+
+    drop(interpreter);
+    assert_eq!(output, [2, 2, 2]);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_snapshot_and_restore() -> Result<(), HandError> {
+    let (_, instructions) =
+        crate::parse_hand_code(include_str!("../../test2.hand").trim()).unwrap();
+    let mut output = Vec::new();
+    let mut interpreter = Interpreter::new(&instructions, io::empty(), &mut output)?;
+    for _ in 0..200 {
+        interpreter.step()?;
+    }
+    let snapshot = interpreter.snapshot();
+
+    interpreter.run()?;
+    assert_ne!(interpreter.snapshot(), snapshot);
+    interpreter.restore(&snapshot).unwrap();
+    assert_eq!(interpreter.snapshot(), snapshot);
+    assert_eq!(interpreter.tape().touched(), snapshot.cells);
+    assert_eq!(interpreter.cursor(), snapshot.cursor);
+    assert_eq!(interpreter.flow_offset(), snapshot.flow_offset);
+    assert_eq!(interpreter.steps(), 200);
+
+    // running on from the snapshot, taken once H was out, prints the rest again
+    interpreter.run()?;
+    drop(interpreter);
+    assert_eq!(output, b"Hello World!\nello World!\n");
+
+    // tapes that grew left and sparse tapes are rebuilt the same way
+    let (_, instructions) = crate::parse_hand_code("👈👈👆👆👉👆👉👉👉👉👆👈👈").unwrap();
+    for tape in [crate::TapeKind::Contiguous, crate::TapeKind::Sparse] {
+        let config = InterpreterConfig {
+            boundary: crate::BoundaryPolicy::Grow,
+            tape,
+            ..InterpreterConfig::default()
+        };
+        let mut interpreter =
+            Interpreter::with_config(&config, translate(&instructions)?, io::empty(), io::sink());
+        interpreter.run()?;
+        let snapshot = interpreter.snapshot();
+        assert_eq!(*interpreter.buffer(), [2, 1, 0, 0, 0, 1]);
+        assert_eq!((snapshot.first_cell, snapshot.cell_count), (-2, 6));
+        assert_eq!(snapshot.cursor, 1);
+
+        interpreter.reset();
+        interpreter.restore(&snapshot).unwrap();
+        assert_eq!(interpreter.snapshot(), snapshot);
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn test_state_of_far_apart_cells() -> Result<(), TapeError> {
+    let config = InterpreterConfig {
+        tape: crate::TapeKind::Sparse,
+        ..InterpreterConfig::default()
+    };
+    let mut interpreter =
+        Interpreter::with_config(&config, translate(&[]).unwrap(), io::empty(), io::sink());
+    interpreter.poke(0, 1)?;
+    interpreter.write_at(1 << 40, 2, IndexPolicy::Grow)?;
+    interpreter.poke(3, 3)?;
+
+    // nothing here copies the 2^40 cells in between
+    assert_eq!(interpreter.memory().to_string(), "[1] 0 0 3 ... 2");
+    let snapshot = interpreter.snapshot();
+    assert_eq!(snapshot.cells, [(0, 1), (3, 3), (1 << 40, 2)]);
+    interpreter.reset();
+    interpreter.restore(&snapshot)?;
+    assert_eq!(interpreter.snapshot(), snapshot);
+
+    let state = interpreter.into_state();
+    assert_eq!(state.cell_count, (1 << 40) + 1);
+    assert_eq!(
+        crate::dump_state(&state, crate::CellWidth::U8),
+        "cursor: 0\ninstructions executed: 0\nfirst cell: 0\n\
+         00000000: 0100 0003 0000 0000 0000 0000 0000 0000  ................\n\
+         *\n\
+         10000000000: 02                                       .\n"
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn test_decimal_output() -> Result<(), HandError> {
+    let (_, instructions) = crate::parse_hand_code("👆👆👆👆👆👊").unwrap();
+    let run = |config: &InterpreterConfig| -> Result<Vec<u8>, HandError> {
+        let mut output = Vec::new();
+        Interpreter::with_config(config, translate(&instructions)?, io::empty(), &mut output)
+            .run()?;
+        Ok(output)
+    };
+    let decimal = InterpreterConfig {
+        output: OutputMode::Decimal,
+        ..InterpreterConfig::default()
+    };
+
+    assert_eq!(run(&decimal)?, b"5 ");
+    assert_eq!(run(&InterpreterConfig::default())?, [5]);
+
+    let (_, instructions) = crate::parse_hand_code("👇👊👆👊👆👊").unwrap();
+    let mut output = Vec::new();
+    let config = InterpreterConfig {
+        signed: true,
+        ..decimal.clone()
+    };
+    Interpreter::with_config(&config, translate(&instructions)?, io::empty(), &mut output).run()?;
+    assert_eq!(output, b"-1 0 1 ");
+    output.clear();
+    Interpreter::with_config(
+        &decimal,
+        translate(&instructions)?,
+        io::empty(),
+        &mut output,
+    )
+    .run()?;
+    assert_eq!(output, b"255 0 1 ");
+
+    Ok(())
+}
+
+#[test]
+pub fn test_overflow_events() -> Result<(), HandError> {
+    use crate::OverflowHook;
+    use std::sync::{Arc, Mutex};
+
+    let code = format!("{}👉👇👇{}", "👆".repeat(256), "👆".repeat(300));
+    let (_, instructions) = crate::parse_hand_code(&code).unwrap();
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let collected = Arc::clone(&events);
+    let config = InterpreterConfig {
+        on_overflow: Some(OverflowHook::new(move |event| {
+            collected.lock().unwrap().push(*event)
+        })),
+        ..InterpreterConfig::default()
+    };
+    let mut interpreter = Interpreter::with_config(
+        &config,
+        crate::compile(&instructions)?,
+        io::empty(),
+        io::sink(),
+    );
+
+    interpreter.run()?;
+
+    assert_eq!(*interpreter.buffer(), [0, 42]);
+    let expected = [
+        OverflowEvent {
+            value: 0,
+            carry: 1,
+            cursor: 0,
+            offset: 255,
+        },
+        OverflowEvent {
+            value: 254,
+            carry: -1,
+            cursor: 1,
+            offset: 257,
+        },
+        OverflowEvent {
+            value: 42,
+            carry: 2,
+            cursor: 1,
+            offset: 260,
+        },
+    ];
+    assert_eq!(*events.lock().unwrap(), expected);
+
+    // the multiply loop runs one step at a time so its wraps are seen too
+    events.lock().unwrap().clear();
+    let code = format!("👆👆🤜👇👉{}👈🤛", "👆".repeat(200));
+    let (_, instructions) = crate::parse_hand_code(&code).unwrap();
+    let mut interpreter = Interpreter::with_config(
+        &config,
+        crate::compile(&instructions)?,
+        io::empty(),
+        io::sink(),
+    );
+    interpreter.run()?;
+    assert_eq!(interpreter.buffer()[1], 144);
+    assert_eq!(events.lock().unwrap().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_lazy_jumps() -> Result<(), HandError> {
+    let unreachable = "🤜👊🤜👆👊🤛✋🤜🤜🤛🤛🤛";
+    let code = format!(
+        "{}{}👉{}",
+        unreachable,
+        include_str!("../../test2.hand").trim(),
+        unreachable
+    );
+    let (_, instructions) = crate::parse_hand_code(&code).unwrap();
+    let config = InterpreterConfig::default();
+
+    let mut expected = Vec::new();
+    crate::run_hand_ast(io::empty(), &mut expected, &instructions)?;
+    let mut output = Vec::new();
+    let mut lazy = Interpreter::with_lazy_jumps(&config, &instructions, io::empty(), &mut output);
+    lazy.run()?;
+
+    let jumps = lazy.lazy_jumps.clone().unwrap();
+    drop(lazy);
+    assert_eq!(output, expected);
+    assert_eq!(output, b"Hello World!\n");
+    let wormholes = crate::calc_wormholes(&instructions)?;
+    for (from, to) in &jumps {
+        assert_eq!(wormholes[from], *to);
+    }
+    // looking for the end of the first loop also finds the loops inside it
+    assert_eq!((jumps[&0], jumps[&2], jumps[&8]), (11, 5, 9));
+
+    let (_, unmatched) = crate::parse_hand_code("👊🤛👆👊").unwrap();
+    let mut output = Vec::new();
+    Interpreter::with_lazy_jumps(&config, &unmatched, io::empty(), &mut output).run()?;
+    assert_eq!(output, [0, 1]);
+    let (_, unmatched) = crate::parse_hand_code("👊🤜👆🤜🤛").unwrap();
+    let result = Interpreter::with_lazy_jumps(&config, &unmatched, io::empty(), io::sink()).run();
+    assert!(matches!(
+        result,
+        Err(HandError::UnmatchedLoopStart { offset: 1 })
+    ));
+    let (_, unmatched) = crate::parse_hand_code("👆🤛").unwrap();
+    let result = Interpreter::with_lazy_jumps(&config, &unmatched, io::empty(), io::sink()).run();
+    assert!(matches!(
+        result,
+        Err(HandError::UnmatchedLoopEnd { offset: 1 })
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+// The reader and writer traits the interpreter works with. With the std
+// feature they are the ones from std::io, without it a small copy that only
+// has what running a program needs.
+#[cfg(feature = "std")]
+pub use std::io::*;
+
+#[cfg(not(feature = "std"))]
+pub use self::core_io::*;
+
+#[cfg(not(feature = "std"))]
+mod core_io {
+    use alloc::collections::VecDeque;
+    use alloc::vec::Vec;
+    use core::fmt;
+
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+    pub enum ErrorKind {
+        Interrupted, // the call can simply be made again
+        BrokenPipe,  // the other end stopped reading
+        WriteZero,   // write_all could not write everything
+        Other,
+    }
+
+    #[derive(Debug)]
+    pub struct Error {
+        kind: ErrorKind,
+    }
+
+    impl Error {
+        pub fn kind(&self) -> ErrorKind {
+            self.kind
+        }
+    }
+
+    impl From<ErrorKind> for Error {
+        fn from(kind: ErrorKind) -> Self {
+            Error { kind }
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{:?}", self.kind)
+        }
+    }
+
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    pub trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+    }
+
+    pub trait Write {
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+
+        fn flush(&mut self) -> Result<()>;
+
+        fn write_all(&mut self, mut buf: &[u8]) -> Result<()> {
+            while !buf.is_empty() {
+                match self.write(buf) {
+                    Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                    Ok(n) => buf = &buf[n..],
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    impl<R: Read + ?Sized> Read for &mut R {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            (**self).read(buf)
+        }
+    }
+
+    impl Read for &[u8] {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            let (head, tail) = self.split_at(n);
+            buf[..n].copy_from_slice(head);
+            *self = tail;
+            Ok(n)
+        }
+    }
+
+    // Reading takes the bytes off the front, like std does.
+    impl Read for VecDeque<u8> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+            let n = buf.len().min(self.len());
+            for (slot, byte) in buf.iter_mut().zip(self.drain(..n)) {
+                *slot = byte;
+            }
+            Ok(n)
+        }
+    }
+
+    impl<W: Write + ?Sized> Write for &mut W {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            (**self).write(buf)
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            (**self).flush()
+        }
+    }
+
+    impl Write for Vec<u8> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    pub struct Empty;
+
+    pub fn empty() -> Empty {
+        Empty
+    }
+
+    impl Read for Empty {
+        fn read(&mut self, _: &mut [u8]) -> Result<usize> {
+            Ok(0)
+        }
+    }
+
+    pub struct Sink;
+
+    pub fn sink() -> Sink {
+        Sink
+    }
+
+    impl Write for Sink {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+}
+
+// Only uses what both versions of this module have, so it compiles the same
+// way against either of them.
+#[test]
+pub fn test_core_hello_world() {
+    struct Collect(alloc::vec::Vec<u8>);
+
+    impl Write for Collect {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            self.0.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    let code = include_str!("../../test2.hand").trim();
+    let instructions = crate::parse_program(code).unwrap();
+    let mut output = Collect(alloc::vec::Vec::new());
+
+    crate::run_hand_ast(empty(), &mut output, &instructions).unwrap();
+    crate::run_hand_ast(&b"ignored"[..], sink(), &instructions).unwrap();
+
+    assert_eq!(output.0, b"Hello World!\n");
+    assert!(crate::calc_wormholes(&instructions).is_ok());
+}
@@ -0,0 +1,174 @@
+use crate::{parse_program, HandError, Instruction};
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub offset: usize, // instruction the diagnostic points at, a byte offset for Parse
+    pub message: String,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+    Parse,              // the source could not be parsed, nothing else is checked
+    UnmatchedLoopStart, // 🤜 without a 🤛
+    UnmatchedLoopEnd,   // 🤛 without a 🤜
+    InfiniteLoop,       // 🤜🤛 never ends once entered
+    InfiniteOutput,     // a loop that prints but never changes the cell it tests, like 🤜👊🤛
+    PointerUnderflow,   // 👈 past cell 0 before anything could have changed the flow
+}
+
+// Checks a program without running it, in the order the problems appear.
+pub fn lint(source: &str) -> Vec<Diagnostic> {
+    let instructions = match parse_program(source) {
+        Ok(instructions) => instructions,
+        Err(e) => {
+            let offset = match e {
+                HandError::Parse { offset, .. } => offset,
+                _ => 0,
+            };
+            return vec![Diagnostic {
+                kind: DiagnosticKind::Parse,
+                offset,
+                message: e.to_string(),
+            }];
+        }
+    };
+    let mut diagnostics = Vec::new();
+    let diagnostic = |kind, offset, message: &str| Diagnostic {
+        kind,
+        offset,
+        message: message.to_string(),
+    };
+
+    let mut starts = Vec::new();
+    for (offset, &ins) in instructions.iter().enumerate() {
+        match ins {
+            Instruction::LoopStart => starts.push(offset),
+            Instruction::LoopEnd => match starts.pop() {
+                Some(start) if start + 1 == offset => diagnostics.push(diagnostic(
+                    DiagnosticKind::InfiniteLoop,
+                    start,
+                    "empty loop never ends once entered",
+                )),
+                Some(start) if prints_forever(&instructions[start + 1..offset]) => diagnostics
+                    .push(diagnostic(
+                        DiagnosticKind::InfiniteOutput,
+                        start,
+                        "loop prints forever once entered, it never changes its cell",
+                    )),
+                Some(_) => {}
+                None => diagnostics.push(diagnostic(
+                    DiagnosticKind::UnmatchedLoopEnd,
+                    offset,
+                    "loop end has no matching start",
+                )),
+            },
+            _ => {}
+        }
+    }
+    diagnostics.extend(starts.into_iter().map(|start| {
+        diagnostic(
+            DiagnosticKind::UnmatchedLoopStart,
+            start,
+            "loop start has no matching end",
+        )
+    }));
+
+    // until the first loop the pointer position is known exactly
+    let mut cursor = 0usize;
+    for (offset, &ins) in instructions.iter().enumerate() {
+        match ins {
+            Instruction::LoopStart | Instruction::LoopEnd => break,
+            Instruction::Next => cursor += 1,
+            Instruction::Previous if cursor == 0 => {
+                diagnostics.push(diagnostic(
+                    DiagnosticKind::PointerUnderflow,
+                    offset,
+                    "pointer always moves left of cell 0 here",
+                ));
+                break;
+            }
+            Instruction::Previous => cursor -= 1,
+            _ => {}
+        }
+    }
+
+    diagnostics.sort_by_key(|d| d.offset);
+    diagnostics
+}
+
+// A loop body without loops inside that prints, comes back to the cell it
+// started on and leaves that cell as it was. This is a guess on the safe side:
+// a body that counts the cell up is left alone since cells wrap back to 0.
+fn prints_forever(body: &[Instruction]) -> bool {
+    let mut position = 0isize;
+    let mut change = 0isize;
+    for &ins in body {
+        match ins {
+            Instruction::LoopStart | Instruction::LoopEnd => return false,
+            Instruction::Next => position += 1,
+            Instruction::Previous => position -= 1,
+            Instruction::Increment if position == 0 => change += 1,
+            Instruction::Decrease if position == 0 => change -= 1,
+            Instruction::Input if position == 0 => return false,
+            _ => {}
+        }
+    }
+    position == 0 && change == 0 && body.contains(&Instruction::Print)
+}
+
+#[test]
+pub fn test_lint_clean_program() {
+    assert_eq!(lint(include_str!("../../test2.hand")), vec![]);
+}
+
+#[test]
+pub fn test_lint_diagnostics() {
+    let kinds = |source| {
+        lint(source)
+            .into_iter()
+            .map(|d| (d.kind, d.offset))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(kinds("👆👉👋"), vec![(DiagnosticKind::Parse, 8)]);
+    assert_eq!(
+        kinds("👆🤛👆🤜👉"),
+        vec![
+            (DiagnosticKind::UnmatchedLoopEnd, 1),
+            (DiagnosticKind::UnmatchedLoopStart, 3)
+        ]
+    );
+    assert_eq!(
+        kinds("👆🤜👉🤜🤛👈👇🤛"),
+        vec![(DiagnosticKind::InfiniteLoop, 3)]
+    );
+    assert_eq!(
+        kinds("👉👆👈👊👈🤜🤛"),
+        vec![
+            (DiagnosticKind::PointerUnderflow, 4),
+            (DiagnosticKind::InfiniteLoop, 5)
+        ]
+    );
+    // after a loop the position is unknown
+    assert_eq!(kinds("👆🤜👉👇🤛👈👈"), vec![]);
+
+    // loops that print
+    assert_eq!(kinds("🤜👊🤛"), vec![(DiagnosticKind::InfiniteOutput, 0)]);
+    assert_eq!(kinds("🤜👊👇🤛"), vec![]);
+    assert_eq!(kinds("👆🤜👊🤛"), vec![(DiagnosticKind::InfiniteOutput, 1)]);
+    assert_eq!(kinds("👆🤜👊👇🤛"), vec![]);
+    assert_eq!(
+        kinds("👆🤜👉👆👊👈👆👇🤛"),
+        vec![(DiagnosticKind::InfiniteOutput, 1)]
+    );
+    assert_eq!(kinds("👆🤜👉👊🤛"), vec![]);
+    assert_eq!(kinds("👆🤜👊✋🤛"), vec![]);
+    assert_eq!(kinds("👆🤜👆👊🤛"), vec![]);
+
+    let diagnostics = lint("🤜");
+    assert_eq!(diagnostics[0].message, "loop start has no matching end");
+}
@@ -1,166 +1,394 @@
-use nom::{
-    branch::alt,
-    character::complete::{char, multispace0},
-    combinator::{eof, value},
-    multi::many0,
-    sequence::{pair, preceded, terminated},
-    IResult,
+use rust_interpreter::{compile, to_c, trace_line, translate, validate};
+use rust_interpreter::{
+    dump_state, explain, parse_program_with_input, run_hand_ast_with_config, to_brainfuck,
 };
-use std::collections::{HashMap, VecDeque};
-use std::io;
+use rust_interpreter::{HandError, Interpreter, InterpreterConfig, LineEnding, Repl};
 use std::io::prelude::*;
+use std::{fs, io, process};
 
-#[derive(Copy, Clone)]
-pub enum Instruction {
-    Next,      // 👉 : moves the memory pointer to the next cell
-    Previous,  // 👈 : moves the memory pointer to the previous cell
-    Increment, // 👆 : increment the memory cell at the current position
-    Decrease,  // 👇 : decreases the memory cell at the current position
-    LoopStart, // 🤜 : if the memory cell at the current position is 0, jump just after the corresponding 🤛
-    LoopEnd, // 🤛 : if the memory cell at the current position is not 0, jump just after the corresponding 🤜
-    Print, // 👊 : Display the current character represented by the ASCII code defined by the current position.
+enum Emit {
+    Brainfuck,
+    C,
+    Explain,
 }
 
-fn main() -> io::Result<()> {
-    println!("Hand Interpreter!");
-    let mut buffer = String::new();
-    let mut reader = io::stdin();
-    reader.read_to_string(&mut buffer)?;
+#[derive(Default)]
+struct Options {
+    emit: Option<Emit>,
+    path: Option<String>, // read the program from this file instead of stdin
+    input: Option<Input>, // where ✋ reads from, stdin when not given
+    repl: bool,
+    dump: bool,  // print the machine state to stderr after the run
+    trace: bool, // print every instruction to stderr as it runs
+    config: InterpreterConfig,
+}
 
-    let (_, instructions) = parse_hand_code(buffer.as_str()).unwrap();
+enum Input {
+    File(String),
+    Text(String),
+}
 
-    run_hand_ast(io::stdout(), &instructions)?;
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--emit" => {
+                options.emit = match args.next().as_deref() {
+                    Some("brainfuck") => Some(Emit::Brainfuck),
+                    Some("c") => Some(Emit::C),
+                    Some("explain") => Some(Emit::Explain),
+                    Some(other) => return Err(format!("unknown --emit target {:?}", other)),
+                    None => return Err("--emit needs a target".to_string()),
+                }
+            }
+            "--repl" => options.repl = true,
+            "--dump" => options.dump = true,
+            "--trace" => options.trace = true,
+            "--crlf" => options.config.line_ending = LineEnding::CrLf,
+            "--max-steps" => match args.next().map(|n| n.parse()) {
+                Some(Ok(limit)) => options.config.max_steps = Some(limit),
+                Some(Err(_)) => return Err("--max-steps needs a whole number".to_string()),
+                None => return Err("--max-steps needs a limit".to_string()),
+            },
+            "--input" => match args.next() {
+                Some(path) => options.input = Some(Input::File(path)),
+                None => return Err("--input needs a file".to_string()),
+            },
+            "--input-string" => match args.next() {
+                Some(text) => options.input = Some(Input::Text(text)),
+                None => return Err("--input-string needs a value".to_string()),
+            },
+            _ if arg.starts_with("--") => return Err(format!("unknown argument {:?}", arg)),
+            _ if options.path.is_none() => options.path = Some(arg),
+            _ => return Err(format!("unexpected argument {:?}", arg)),
+        }
+    }
+    Ok(options)
+}
 
-    Ok(())
+// The bytes are checked for UTF-8 when parsing, so a bad file reports where
+// the problem is.
+fn read_source(path: Option<&str>) -> io::Result<Vec<u8>> {
+    match path {
+        Some(path) => fs::read(path),
+        None => {
+            let mut buffer = Vec::new();
+            io::stdin().read_to_end(&mut buffer)?;
+            Ok(buffer)
+        }
+    }
 }
 
-pub fn run_hand_ast(mut writer: impl Write, instructions: &Vec<Instruction>) -> io::Result<()> {
-    use Instruction::*;
-    let mut buffer = vec![0u8];
-    let mut cursor = 0usize;
-    let mut flow_offset = 0usize;
-    let wormholes_map = calc_wormholes(instructions.clone());
+fn open_input(input: Option<&Input>) -> io::Result<Box<dyn Read>> {
+    Ok(match input {
+        Some(Input::File(path)) => Box::new(fs::File::open(path)?),
+        Some(Input::Text(text)) => Box::new(io::Cursor::new(text.clone().into_bytes())),
+        None => Box::new(io::stdin()),
+    })
+}
 
-    while let Some(ins) = instructions.get(flow_offset) {
-        match ins {
-            Next => {
-                cursor = cursor + 1;
-                if let None = buffer.get(cursor) {
-                    buffer.push(0u8);
-                }
-            }
-            Previous => {
-                cursor = cursor - 1;
-            }
-            Increment => {
-                if let Some(v) = buffer.get_mut(cursor) {
-                    let (add, _) = (*v).overflowing_add(1u8);
-                    *v = add;
-                }
-            }
-            LoopStart => {
-                if let Some(v) = buffer.get(cursor) {
-                    if *v == 0 {
-                        flow_offset = *wormholes_map.get(&flow_offset).unwrap_or(&0usize);
-                    }
-                }
-            }
-            LoopEnd => {
-                if let Some(v) = buffer.get(cursor) {
-                    if *v != 0 {
-                        flow_offset = *wormholes_map.get(&flow_offset).unwrap_or(&0usize);
-                    }
-                }
-            }
-            Decrease => {
-                if let Some(v) = buffer.get_mut(cursor) {
-                    let (sub, _) = (*v).overflowing_sub(1u8);
-                    *v = sub;
-                }
-            }
-            Print => {
-                if let Some(b) = buffer.get(cursor) {
-                    writer.write(&(*b).to_be_bytes())?;
-                }
+// The program's output goes to writer, the machine state for --dump to dump.
+fn run(options: &Options, mut writer: impl Write, mut dump: impl Write) -> Result<(), HandError> {
+    let source = read_source(options.path.as_deref())?;
+    let source = String::from_utf8(source).map_err(|e| HandError::InvalidEncoding {
+        offset: e.utf8_error().valid_up_to(),
+    })?;
+    // --input and --input-string win over an !input line in the program
+    let (instructions, inline) = parse_program_with_input(&source)?;
+    let inline = inline.map(Input::Text);
+    let input = options.input.as_ref().or(inline.as_ref());
+
+    match options.emit {
+        Some(Emit::Brainfuck) => {
+            writeln!(writer, "{}", to_brainfuck(&instructions))?;
+            Ok(())
+        }
+        Some(Emit::C) => {
+            write!(writer, "{}", to_c(&instructions))?;
+            Ok(())
+        }
+        Some(Emit::Explain) => {
+            write!(writer, "{}", explain(&instructions))?;
+            Ok(())
+        }
+        None if options.trace => {
+            // translated rather than compiled, so every step is one instruction
+            validate(&instructions, &options.config)?;
+            let bytecode = translate(&instructions)?;
+            let reader = open_input(input)?;
+            let mut interpreter =
+                Interpreter::with_config(&options.config, bytecode, reader, writer);
+            let mut stderr = io::BufWriter::new(io::stderr());
+            interpreter.set_trace(move |step| {
+                let _ = writeln!(stderr, "{}", trace_line(instructions[step.offset], step));
+            });
+            let result = interpreter.run();
+            if !options.dump {
+                return result;
             }
+            // the state where a run failed is the one most worth seeing
+            let state = interpreter.into_state();
+            let dumped = write!(dump, "{}", dump_state(&state, options.config.cell_width));
+            result.and(dumped.map_err(HandError::from))
+        }
+        None if options.dump => {
+            validate(&instructions, &options.config)?;
+            let bytecode = compile(&instructions)?;
+            let reader = open_input(input)?;
+            let mut interpreter =
+                Interpreter::with_config(&options.config, bytecode, reader, writer);
+            let result = interpreter.run();
+            let state = interpreter.into_state();
+            let dumped = write!(dump, "{}", dump_state(&state, options.config.cell_width));
+            result.and(dumped.map_err(HandError::from))
+        }
+        None => {
+            let reader = open_input(input)?;
+            run_hand_ast_with_config(&options.config, reader, writer, &instructions)
+        }
+    }
+}
+
+// Reads lines of code from the reader and prints the tape after each one. ✋
+// always sees the end of input, the reader is only used for code.
+fn repl(
+    config: &InterpreterConfig,
+    reader: impl BufRead,
+    mut writer: impl Write,
+) -> io::Result<()> {
+    let mut repl = Repl::new(config);
+    write!(writer, "> ")?;
+    writer.flush()?;
+    for line in reader.lines() {
+        let mut output = Vec::new();
+        let result = repl.eval(&line?, io::empty(), &mut output);
+        writer.write_all(&output)?;
+        if !output.is_empty() && !output.ends_with(b"\n") {
+            writeln!(writer)?;
         }
-        flow_offset += 1;
+        match result {
+            Ok(()) => writeln!(writer, "{}", repl.memory())?,
+            Err(e) => writeln!(writer, "error: {}", e)?,
+        }
+        write!(writer, "> ")?;
+        writer.flush()?;
     }
+    writeln!(writer)
+}
 
-    Ok(())
+// Running out of steps gets its own status so callers can tell it from a crash.
+fn exit_status(error: &HandError) -> i32 {
+    match error {
+        HandError::StepLimitExceeded { .. } => 3,
+        _ => 1,
+    }
 }
 
-fn calc_wormholes(instructions: Vec<Instruction>) -> HashMap<usize, usize> {
-    let mut offset = 0usize;
-    let mut stack = VecDeque::from(instructions);
-    let mut map = HashMap::new();
-    let mut starts = Vec::new();
+fn main() {
+    let options = parse_args(std::env::args().skip(1)).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        process::exit(2);
+    });
 
-    while let Some(ins) = stack.pop_front() {
-        match ins {
-            Instruction::LoopStart => {
-                starts.push(offset);
-            }
-            Instruction::LoopEnd => {
-                if let Some(start) = starts.pop() {
-                    map.insert(start, offset);
-                    map.insert(offset, start);
-                }
-            }
-            _ => (),
+    if options.emit.is_none() {
+        let _ = writeln!(io::stdout(), "Hand Interpreter!");
+    }
+    if options.repl {
+        if let Err(e) = repl(&options.config, io::stdin().lock(), io::stdout()) {
+            eprintln!("error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+    let mut stdout = io::BufWriter::new(io::stdout().lock());
+    let result = run(&options, &mut stdout, io::stderr())
+        .and_then(|()| stdout.flush().map_err(HandError::from));
+    match result {
+        Err(HandError::Io(e)) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("error: {}", e);
+            process::exit(exit_status(&e));
         }
-        offset += 1;
+        Ok(()) => {}
     }
+}
+
+#[test]
+fn test_read_source_from_file() -> io::Result<()> {
+    let path = std::env::temp_dir().join(format!("hand-{}.hand", process::id()));
+    fs::write(&path, "👆👊")?;
+
+    let source = read_source(path.to_str());
+    fs::remove_file(&path)?;
+
+    assert_eq!(source?, "👆👊".as_bytes());
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_args() {
+    let args = |list: &[&str]| parse_args(list.iter().map(|s| s.to_string()));
 
-    map
-}
-
-pub fn parse_hand_code(input: &str) -> IResult<&str, Vec<Instruction>> {
-    use Instruction::*;
-    let keychar = |c| preceded(multispace0, char(c));
-    let ins = |c, v| value(v, keychar(c));
-    let next_ins = ins('👉', Next);
-    let prev_ins = ins('👈', Previous);
-    let incr_ins = ins('👆', Increment);
-    let decr_ins = ins('👇', Decrease);
-    let lost_ins = ins('🤜', LoopStart);
-    let lond_ins = ins('🤛', LoopEnd);
-    let prnt_ins = ins('👊', Print);
-    let ins_alter = alt((
-        next_ins, prev_ins, incr_ins, decr_ins, lost_ins, lond_ins, prnt_ins,
+    let options = args(&["program.hand", "--emit", "brainfuck"]).unwrap();
+    assert_eq!(options.path.as_deref(), Some("program.hand"));
+    assert!(matches!(options.emit, Some(Emit::Brainfuck)));
+    assert!(matches!(
+        args(&["--emit", "c"]).unwrap().emit,
+        Some(Emit::C)
     ));
-    let mut instructions = terminated(many0(ins_alter), pair(multispace0, eof));
-    instructions(input)
+    assert!(matches!(
+        args(&["--emit", "explain"]).unwrap().emit,
+        Some(Emit::Explain)
+    ));
+
+    assert!(args(&[]).unwrap().path.is_none());
+    assert!(args(&["--repl"]).unwrap().repl);
+    assert!(args(&["--dump", "program.hand"]).unwrap().dump);
+    assert!(!args(&["program.hand"]).unwrap().dump);
+    assert!(args(&["--trace", "program.hand"]).unwrap().trace);
+    assert!(!args(&["program.hand"]).unwrap().trace);
+    let options = args(&["--max-steps", "1000", "program.hand"]).unwrap();
+    assert_eq!(options.config.max_steps, Some(1000));
+    assert_eq!(args(&[]).unwrap().config.max_steps, None);
+    assert!(args(&["--max-steps", "lots"]).is_err());
+    let options = args(&["--crlf", "program.hand"]).unwrap();
+    assert_eq!(options.config.line_ending, LineEnding::CrLf);
+    assert_eq!(args(&[]).unwrap().config.line_ending, LineEnding::Unchanged);
+    assert!(args(&["a.hand", "b.hand"]).is_err());
+    assert!(args(&["--bogus"]).is_err());
 }
 
 #[test]
-pub fn test_hello() -> io::Result<()> {
-    let code =
-        "👇🤜👇👇👇👇👇👇👇👉👆👈🤛👉👇👊👇🤜👇👉👆👆👆👆👆👈🤛👉👆👆👊👆👆👆👆👆👆👆👊👊👆👆👆👊";
-    let buf = Vec::new();
-    let mut writer = io::BufWriter::new(buf);
-    let (_, instructions) = parse_hand_code(code).unwrap();
+fn test_run_with_input() -> Result<(), HandError> {
+    let dir = std::env::temp_dir();
+    let program = dir.join(format!("hand-echo-{}.hand", process::id()));
+    let input = dir.join(format!("hand-echo-{}.txt", process::id()));
+    fs::write(&program, "✋🤜👊✋🤛")?;
+    fs::write(&input, "from a file")?;
+    let program = program.to_str().unwrap();
 
-    run_hand_ast(&mut writer, &instructions)?;
+    let mut from_text = Vec::new();
+    let args = ["--input-string", "from a string", program];
+    let result = run(
+        &parse_args(args.map(String::from).into_iter()).unwrap(),
+        &mut from_text,
+        io::sink(),
+    );
+    let mut from_file = Vec::new();
+    let args = [program, "--input", input.to_str().unwrap()];
+    let result = result.and(run(
+        &parse_args(args.map(String::from).into_iter()).unwrap(),
+        &mut from_file,
+        io::sink(),
+    ));
+    fs::remove_file(program)?;
+    fs::remove_file(&input)?;
 
-    let result = String::from_utf8(writer.into_inner().unwrap()).unwrap();
-    assert_eq!(result, "Hello");
+    result?;
+    assert_eq!(from_text, b"from a string");
+    assert_eq!(from_file, b"from a file");
 
     Ok(())
 }
 
 #[test]
-pub fn test_hello_world() -> io::Result<()> {
-    let code =
-        "👉👆👆👆👆👆👆👆👆🤜👇👈👆👆👆👆👆👆👆👆👆👉🤛👈👊👉👉👆👉👇🤜👆🤛👆👆👉👆👆👉👆👆👆🤜👉🤜👇👉👆👆👆👈👈👆👆👆👉🤛👈👈🤛👉👇👇👇👇👇👊👉👇👉👆👆👆👊👊👆👆👆👊👉👇👊👈👈👆🤜👉🤜👆👉👆🤛👉👉🤛👈👇👇👇👇👇👇👇👇👇👇👇👇👇👇👊👉👉👊👆👆👆👊👇👇👇👇👇👇👊👇👇👇👇👇👇👇👇👊👉👆👊👉👆👊";
-    let buf = Vec::new();
-    let mut writer = io::BufWriter::new(buf);
-    let (_, instructions) = parse_hand_code(code).unwrap();
+fn test_run_with_inline_input() -> Result<(), HandError> {
+    let path = std::env::temp_dir().join(format!("hand-inline-{}.hand", process::id()));
+    fs::write(&path, "!input: inline\n✋🤜👊✋🤛")?;
+    let path = path.to_str().unwrap();
+
+    let mut inline = Vec::new();
+    let result = run(
+        &parse_args([path].map(String::from).into_iter()).unwrap(),
+        &mut inline,
+        io::sink(),
+    );
+    let mut overridden = Vec::new();
+    let args = ["--input-string", "given", path];
+    let result = result.and(run(
+        &parse_args(args.map(String::from).into_iter()).unwrap(),
+        &mut overridden,
+        io::sink(),
+    ));
+    fs::remove_file(path)?;
 
-    run_hand_ast(&mut writer, &instructions)?;
+    result?;
+    assert_eq!(inline, b"inline");
+    assert_eq!(overridden, b"given");
 
-    let result = String::from_utf8(writer.into_inner().unwrap()).unwrap();
-    assert_eq!(result, "Hello World!\n");
+    Ok(())
+}
+
+#[test]
+fn test_repl() -> io::Result<()> {
+    let mut output = Vec::new();
+    repl(
+        &InterpreterConfig::default(),
+        "👆👆\n👆👊👉\n👈👈\n".as_bytes(),
+        &mut output,
+    )?;
+    assert_eq!(
+        String::from_utf8_lossy(&output),
+        "> [2]\n> \u{3}\n3 [0]\n> error: pointer underflow at instruction 1\n> \n"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_run_with_max_steps() -> Result<(), HandError> {
+    let path = std::env::temp_dir().join(format!("hand-forever-{}.hand", process::id()));
+    fs::write(&path, "👆🤜🤛")?;
+    let args = ["--max-steps", "10", path.to_str().unwrap()];
+
+    let result = run(
+        &parse_args(args.map(String::from).into_iter()).unwrap(),
+        io::sink(),
+        io::sink(),
+    );
+    fs::remove_file(&path)?;
+
+    let error = result.unwrap_err();
+    assert!(matches!(
+        error,
+        HandError::StepLimitExceeded { limit: 10, .. }
+    ));
+    assert_eq!(exit_status(&error), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_dump_after_failed_run() -> Result<(), HandError> {
+    let path = std::env::temp_dir().join(format!("hand-dump-{}.hand", process::id()));
+    fs::write(&path, "👆👉👆👆🤜🤛")?;
+    let path = path.to_str().unwrap();
+
+    // both the compiled run and the one --trace translates dump where they stopped
+    let mut dumps = Vec::new();
+    for trace in [None, Some("--trace")] {
+        let args = ["--dump", "--max-steps", "10", path]
+            .into_iter()
+            .chain(trace);
+        let mut dump = Vec::new();
+        let result = run(
+            &parse_args(args.map(String::from)).unwrap(),
+            io::sink(),
+            &mut dump,
+        );
+        assert!(matches!(
+            result,
+            Err(HandError::StepLimitExceeded { limit: 10, .. })
+        ));
+        dumps.push(String::from_utf8(dump).unwrap());
+    }
+    fs::remove_file(path)?;
+
+    for dump in dumps {
+        assert!(dump.starts_with("cursor: 1\n"));
+        assert!(dump.ends_with("00000000: 0102                                     ..\n"));
+    }
 
     Ok(())
 }
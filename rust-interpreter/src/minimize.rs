@@ -0,0 +1,118 @@
+use crate::optimize::matching_end;
+use crate::Instruction;
+use alloc::vec::Vec;
+
+// Shrinks a program while keep still holds for it, such as "still prints this"
+// or "still fails this way". Runs of instructions are removed, starting with
+// half the program and halving down to single instructions, and a run is only
+// removed when it has as many 🤜 as 🤛 in order, so loops stay balanced. The
+// result can't lose any single instruction or loop and still be kept, though a
+// smaller program may exist. keep is called many times, programs that may not
+// end should be run with a step limit.
+pub fn minimize(
+    instructions: &[Instruction],
+    mut keep: impl FnMut(&[Instruction]) -> bool,
+) -> Vec<Instruction> {
+    let mut current = instructions.to_vec();
+    let mut size = (current.len() / 2).max(1);
+    loop {
+        let mut removed = false;
+        let mut start = 0;
+        while start + size <= current.len() {
+            // one at a time a 🤜 takes its whole loop with it, a lone 🤛 never goes
+            let end = match size {
+                1 => loop_end(&current[start..]).map_or(start + 1, |end| start + end + 1),
+                _ => start + size,
+            };
+            if balanced(&current[start..end]) {
+                let mut candidate = current[..start].to_vec();
+                candidate.extend_from_slice(&current[end..]);
+                if keep(&candidate) {
+                    current = candidate;
+                    removed = true;
+                    continue;
+                }
+            }
+            start += 1;
+        }
+        match (removed, size) {
+            // a single removal can make others possible again
+            (true, _) => {}
+            (false, 1) => return current,
+            (false, _) => size /= 2,
+        }
+    }
+}
+
+// Index of the 🤛 closing the loop at the start of the slice, if it starts one.
+fn loop_end(instructions: &[Instruction]) -> Option<usize> {
+    match instructions.first() {
+        Some(Instruction::LoopStart) => matching_end(instructions),
+        _ => None,
+    }
+}
+
+fn balanced(run: &[Instruction]) -> bool {
+    let mut depth = 0usize;
+    for &ins in run {
+        match ins {
+            Instruction::LoopStart => depth += 1,
+            Instruction::LoopEnd if depth == 0 => return false,
+            Instruction::LoopEnd => depth -= 1,
+            _ => {}
+        }
+    }
+    depth == 0
+}
+
+#[test]
+pub fn test_minimize_padded_hello() {
+    use crate::{parse_program, run_hand_ast_with_config, InterpreterConfig};
+
+    let hello = parse_program(include_str!("../../test1.hand")).unwrap();
+    let padded = parse_program(&alloc::format!(
+        "🤜👊👆🤛👉👈 {} 👆👇👉🤜👆👊🤛👈👉",
+        include_str!("../../test1.hand").trim()
+    ))
+    .unwrap();
+    let config = InterpreterConfig {
+        max_steps: Some(10_000),
+        ..InterpreterConfig::default()
+    };
+    let prints_hello = |program: &[Instruction]| {
+        let mut output = Vec::new();
+        let result = run_hand_ast_with_config(&config, crate::io::empty(), &mut output, program);
+        result.is_ok() && output == b"Hello"
+    };
+    assert!(prints_hello(&padded));
+
+    let minimal = minimize(&padded, prints_hello);
+
+    assert!(prints_hello(&minimal));
+    assert!(minimal.len() <= hello.len());
+    for offset in 0..minimal.len() {
+        let end = loop_end(&minimal[offset..]).map_or(offset + 1, |end| offset + end + 1);
+        if balanced(&minimal[offset..end]) {
+            let mut smaller = minimal.clone();
+            smaller.drain(offset..end);
+            assert!(!prints_hello(&smaller));
+        }
+    }
+}
+
+#[test]
+pub fn test_minimize_keeps_loops_balanced() {
+    use Instruction::*;
+
+    // only programs with 👊 in a loop are kept
+    let program = [
+        Increment, LoopStart, Next, Print, Previous, Decrease, LoopEnd, Print,
+    ];
+    let minimal = minimize(&program, |candidate| {
+        assert!(balanced(candidate));
+        crate::iter_with_depth(candidate).any(|(_, ins, depth)| ins == Print && depth > 0)
+    });
+
+    assert_eq!(minimal, [LoopStart, Print, LoopEnd]);
+    assert!(minimize(&[], |_| true).is_empty());
+}
@@ -0,0 +1,202 @@
+use crate::tape::initial_cell;
+use crate::{BoundaryPolicy, Instruction, InterpreterConfig, OverflowMode};
+use alloc::vec::Vec;
+
+// Removes instructions that provably do nothing under the given config:
+// adjacent inverse pairs like 👉👈 or 👆👇, and loops that start on a cell known
+// to be 0 (at the start of the program unless initial_cell_value isn't, or
+// right after another loop). Pairs are only cancelled when no error could have
+// been raised between them, and never across a loop boundary, 👊 or ✋. Error
+// offsets reported for the result refer to the optimized program.
+pub fn eliminate_dead_code(
+    instructions: &[Instruction],
+    config: &InterpreterConfig,
+) -> Vec<Instruction> {
+    let mut output: Vec<Instruction> = Vec::with_capacity(instructions.len());
+    let mut offset = 0;
+
+    while let Some(&ins) = instructions.get(offset) {
+        let cell_is_zero = match output.last() {
+            None => initial_cell(config) == 0,
+            Some(&last) => last == Instruction::LoopEnd,
+        };
+        if ins == Instruction::LoopStart && cell_is_zero {
+            if let Some(end) = matching_end(&instructions[offset..]) {
+                offset += end + 1;
+                continue;
+            }
+        }
+        match output.last() {
+            Some(&previous) if cancels(previous, ins, config) => {
+                output.pop();
+            }
+            _ => output.push(ins),
+        }
+        offset += 1;
+    }
+
+    output
+}
+
+fn cancels(first: Instruction, second: Instruction, config: &InterpreterConfig) -> bool {
+    use Instruction::*;
+    // a clamped move at either end can't be undone by a move back
+    let unlimited = match config.boundary {
+        BoundaryPolicy::Wrap => true,
+        BoundaryPolicy::Clamp => false,
+        BoundaryPolicy::Error | BoundaryPolicy::Grow => config.max_tape_size.is_none(),
+    };
+    match (first, second) {
+        (Next, Previous) => unlimited,
+        (Previous, Next) => unlimited && config.boundary != BoundaryPolicy::Error,
+        (Increment, Decrease) | (Decrease, Increment) => config.overflow == OverflowMode::Wrap,
+        _ => false,
+    }
+}
+
+// What one run through a loop body does, for bodies that only move and add.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LoopEffect {
+    pub cell_delta: isize,    // added to the cell the body started on
+    pub pointer_delta: isize, // how far right the pointer ends up, left when negative
+}
+
+// The body is what is between a 🤜 and its 🤛. With a pointer_delta of 0 the
+// cell_delta is what each run does to the cell the loop tests. None when the
+// body prints, reads or has loops of its own, since then one run doesn't say
+// much about the next.
+pub fn analyze_loop_body(body: &[Instruction]) -> Option<LoopEffect> {
+    let mut effect = LoopEffect {
+        cell_delta: 0,
+        pointer_delta: 0,
+    };
+    for &ins in body {
+        match ins {
+            Instruction::Next => effect.pointer_delta += 1,
+            Instruction::Previous => effect.pointer_delta -= 1,
+            Instruction::Increment if effect.pointer_delta == 0 => effect.cell_delta += 1,
+            Instruction::Decrease if effect.pointer_delta == 0 => effect.cell_delta -= 1,
+            Instruction::Increment | Instruction::Decrease => {}
+            Instruction::LoopStart
+            | Instruction::LoopEnd
+            | Instruction::Print
+            | Instruction::Input => return None,
+        }
+    }
+    Some(effect)
+}
+
+// Index of the 🤛 closing the loop opened at the start of the slice.
+pub(crate) fn matching_end(instructions: &[Instruction]) -> Option<usize> {
+    let mut depth = 0usize;
+    for (index, &ins) in instructions.iter().enumerate() {
+        match ins {
+            Instruction::LoopStart => depth += 1,
+            Instruction::LoopEnd => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[test]
+pub fn test_eliminate_inverse_pairs() {
+    use Instruction::*;
+    let config = InterpreterConfig::default();
+
+    assert_eq!(
+        eliminate_dead_code(&[Next, Previous, Print], &config),
+        vec![Print]
+    );
+    assert_eq!(
+        eliminate_dead_code(
+            &[Increment, Next, Next, Previous, Previous, Decrease],
+            &config
+        ),
+        vec![]
+    );
+    assert_eq!(
+        eliminate_dead_code(&[Increment, Print, Decrease], &config),
+        vec![Increment, Print, Decrease]
+    );
+    assert_eq!(
+        eliminate_dead_code(&[Increment, LoopStart, Decrease, LoopEnd], &config),
+        vec![Increment, LoopStart, Decrease, LoopEnd]
+    );
+    // 👈 at cell 0 fails, so 👈👉 has to stay unless the tape wraps
+    assert_eq!(
+        eliminate_dead_code(&[Previous, Next], &config),
+        vec![Previous, Next]
+    );
+    let wrapping = InterpreterConfig {
+        boundary: BoundaryPolicy::Wrap,
+        ..InterpreterConfig::default()
+    };
+    assert_eq!(eliminate_dead_code(&[Previous, Next], &wrapping), vec![]);
+    // 👆👇 at the largest value is not a no-op once cells saturate
+    let saturating = InterpreterConfig {
+        overflow: OverflowMode::Saturate,
+        ..InterpreterConfig::default()
+    };
+    assert_eq!(
+        eliminate_dead_code(&[Increment, Decrease], &saturating),
+        vec![Increment, Decrease]
+    );
+}
+
+#[test]
+pub fn test_eliminate_dead_loops() {
+    use Instruction::*;
+    let config = InterpreterConfig::default();
+
+    assert_eq!(
+        eliminate_dead_code(
+            &[LoopStart, Increment, LoopStart, LoopEnd, LoopEnd, Increment, Print],
+            &config
+        ),
+        vec![Increment, Print]
+    );
+    assert_eq!(
+        eliminate_dead_code(
+            &[Increment, LoopStart, Decrease, LoopEnd, LoopStart, Print, LoopEnd],
+            &config
+        ),
+        vec![Increment, LoopStart, Decrease, LoopEnd]
+    );
+
+    let code = include_str!("../../test2.hand").trim();
+    let (_, instructions) = crate::parse_hand_code(code).unwrap();
+    let optimized = eliminate_dead_code(&instructions, &config);
+    let mut output = Vec::new();
+    crate::run_hand_ast(std::io::empty(), &mut output, &optimized).unwrap();
+    assert_eq!(output, b"Hello World!\n");
+}
+
+#[test]
+pub fn test_analyze_loop_body() {
+    let effect = |body: &str| analyze_loop_body(&crate::parse_program(body).unwrap());
+    let loop_effect = |cell_delta, pointer_delta| {
+        Some(LoopEffect {
+            cell_delta,
+            pointer_delta,
+        })
+    };
+
+    // clearing and seeking
+    assert_eq!(effect("👇"), loop_effect(-1, 0));
+    assert_eq!(effect("👉👉"), loop_effect(0, 2));
+    assert_eq!(effect(""), loop_effect(0, 0));
+    // multiplying, only the cell the loop started on counts
+    assert_eq!(effect("👇👉👆👆👉👆👈👈"), loop_effect(-1, 0));
+    assert_eq!(effect("👉👇👈👆👆👆"), loop_effect(3, 0));
+    assert_eq!(effect("👆👈👇"), loop_effect(1, -1));
+    // anything else
+    assert_eq!(effect("👇👊"), None);
+    assert_eq!(effect("✋👉"), None);
+    assert_eq!(effect("👇🤜👉🤛"), None);
+}
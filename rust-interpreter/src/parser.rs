@@ -0,0 +1,662 @@
+use crate::{HandError, Instruction};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use nom::{
+    branch::alt,
+    bytes::complete::is_not,
+    character::complete::{anychar, char, multispace1, satisfy},
+    combinator::{eof, map, map_opt, opt, recognize, value},
+    multi::{fold_many0, many0, many0_count},
+    sequence::{pair, preceded, terminated},
+    IResult,
+};
+
+// Takes time linear in the length of the source, about 36ns per instruction
+// for a long program with comments (see bench_scaling in the benchmarks).
+pub fn parse_program(source: &str) -> Result<Vec<Instruction>, HandError> {
+    parse_program_with(source, Dialect::default())
+}
+
+// Same as parse_program for a program written with other symbols.
+pub fn parse_program_with(source: &str, dialect: Dialect) -> Result<Vec<Instruction>, HandError> {
+    let (_, instructions) =
+        parse_hand_code_with(source, dialect).map_err(|e| parse_error(source, e))?;
+    Ok(instructions)
+}
+
+// A program can start with a line like `!input: hello` giving the input for
+// its ✋, so a test program carries its own. The input runs to the end of the
+// line, with \n for a line break and \\ for a backslash. The line isn't part
+// of the program, error offsets still count from the start of the source.
+pub fn parse_program_with_input(
+    source: &str,
+) -> Result<(Vec<Instruction>, Option<String>), HandError> {
+    let Some(line) = source.strip_prefix("!input:") else {
+        return Ok((parse_program(source)?, None));
+    };
+    let end = line.find('\n').map_or(line.len(), |end| end + 1);
+    let skipped = source.len() - line.len() + end;
+    let text = line[..end].trim_end_matches(['\r', '\n']);
+    let text = text.strip_prefix(' ').unwrap_or(text);
+    let instructions = parse_program(&source[skipped..]).map_err(|e| match e {
+        HandError::Parse { offset, message } => HandError::Parse {
+            offset: offset + skipped,
+            message,
+        },
+        e => e,
+    })?;
+    Ok((instructions, Some(unescape(text))))
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let escaped = match (c, chars.peek()) {
+            ('\\', Some('n')) => '\n',
+            ('\\', Some('\\')) => '\\',
+            _ => {
+                unescaped.push(c);
+                continue;
+            }
+        };
+        unescaped.push(escaped);
+        chars.next();
+    }
+    unescaped
+}
+
+// Same as parse_program, but comments and whitespace are kept as nodes of their
+// own in between the instructions, for tools that write the source back out.
+pub fn parse_program_nodes(source: &str) -> Result<Vec<Node>, HandError> {
+    let node = alt((
+        map(comment, |comment: &str| Node::Comment(comment.to_string())),
+        map(multispace1, |space: &str| {
+            Node::Whitespace(space.to_string())
+        }),
+        map(instruction, Node::Instruction),
+    ));
+    let (_, nodes) = terminated(many0(node), eof)(source).map_err(|e| parse_error(source, e))?;
+    Ok(nodes)
+}
+
+// Same as parse_program, with loops matched as the source is read instead of
+// afterwards. Parsing stops at the first 🤛 without a matching 🤜, and the
+// error gives its byte offset in the source rather than its instruction
+// number. A 🤜 left open is reported at its own byte offset once the source
+// has been read.
+pub fn parse_program_balanced(source: &str) -> Result<Vec<Instruction>, HandError> {
+    let unmatched = |offset, message: &str| HandError::Parse {
+        offset,
+        message: message.to_string(),
+    };
+    let mut instructions = Vec::new();
+    let mut starts = Vec::new(); // byte offsets of the 🤜 still open
+    let mut rest = source;
+    loop {
+        (rest, ()) = trivia(rest).map_err(|e| parse_error(source, e))?;
+        let offset = source.len() - rest.len();
+        let Ok((after, ins)) = instruction(rest) else {
+            break;
+        };
+        match ins {
+            Instruction::LoopStart => starts.push(offset),
+            Instruction::LoopEnd if starts.pop().is_none() => {
+                return Err(unmatched(offset, "🤛 has no matching 🤜"));
+            }
+            _ => {}
+        }
+        instructions.push(ins);
+        rest = after;
+    }
+    eof(rest).map_err(|e| parse_error(source, e))?;
+    match starts.last() {
+        Some(&offset) => Err(unmatched(offset, "🤜 has no matching 🤛")),
+        None => Ok(instructions),
+    }
+}
+
+fn parse_error(source: &str, e: nom::Err<nom::error::Error<&str>>) -> HandError {
+    match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => {
+            let offset = source.len() - e.input.len();
+            let message = match e.input.chars().next() {
+                Some(c) => format!("unexpected character {:?}", c),
+                None => "unexpected end of input".to_string(),
+            };
+            HandError::Parse { offset, message }
+        }
+        nom::Err::Incomplete(_) => HandError::Parse {
+            offset: source.len(),
+            message: "incomplete input".to_string(),
+        },
+    }
+}
+
+// A piece of the source as parse_program_nodes sees it. Modifiers after a hand
+// are not kept, the hand stands for them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Node {
+    Instruction(Instruction),
+    Comment(String),    // from the # to the end of the line, without the line break
+    Whitespace(String), // spaces, tabs and line breaks between the other nodes
+}
+
+// Same as parse_program for raw bytes, such as a file or fuzzer input. Invalid
+// UTF-8 fails with InvalidEncoding at the first byte of the bad sequence.
+pub fn parse_program_bytes(source: &[u8]) -> Result<Vec<Instruction>, HandError> {
+    let source = core::str::from_utf8(source).map_err(|e| HandError::InvalidEncoding {
+        offset: e.valid_up_to(),
+    })?;
+    parse_program(source)
+}
+
+pub fn parse_hand_code(input: &str) -> IResult<&str, Vec<Instruction>> {
+    parse_hand_code_with(input, Dialect::default())
+}
+
+pub fn parse_hand_code_with(input: &str, dialect: Dialect) -> IResult<&str, Vec<Instruction>> {
+    let mut instructions = terminated(
+        many0(preceded(trivia, dialect_instruction(dialect))),
+        pair(trivia, eof),
+    );
+    instructions(input)
+}
+
+// The symbol for each instruction. Whitespace, comments and the modifiers
+// after a symbol are skipped the same way whatever the symbols are, so a
+// dialect can't use whitespace or #.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Dialect {
+    pub next: char,
+    pub previous: char,
+    pub increment: char,
+    pub decrease: char,
+    pub loop_start: char,
+    pub loop_end: char,
+    pub print: char,
+    pub input: char,
+}
+
+impl Dialect {
+    pub fn instruction(&self, c: char) -> Option<Instruction> {
+        use Instruction::*;
+        [
+            (self.next, Next),
+            (self.previous, Previous),
+            (self.increment, Increment),
+            (self.decrease, Decrease),
+            (self.loop_start, LoopStart),
+            (self.loop_end, LoopEnd),
+            (self.print, Print),
+            (self.input, Input),
+        ]
+        .into_iter()
+        .find_map(|(symbol, ins)| (symbol == c).then_some(ins))
+    }
+}
+
+// The hands.
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect {
+            next: '👉',
+            previous: '👈',
+            increment: '👆',
+            decrease: '👇',
+            loop_start: '🤜',
+            loop_end: '🤛',
+            print: '👊',
+            input: '✋',
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Span {
+    pub offset: usize, // byte offset of the instruction in the source
+    pub line: usize,   // 1-based line number
+    pub column: usize, // 1-based column, counted in characters
+}
+
+pub fn parse_hand_code_spanned<'a>(input: &'a str) -> IResult<&'a str, Vec<(Instruction, Span)>> {
+    let positioned = |i: &'a str| -> IResult<&'a str, (Instruction, usize)> {
+        let (rest, ins) = instruction(i)?;
+        Ok((rest, (ins, input.len() - i.len())))
+    };
+    let mut instructions = terminated(many0(preceded(trivia, positioned)), pair(trivia, eof));
+    let (rest, positioned) = instructions(input)?;
+
+    let mut line = 1usize;
+    let mut column = 1usize;
+    let mut chars = input.char_indices().peekable();
+    let spanned = positioned
+        .into_iter()
+        .map(|(ins, offset)| {
+            while let Some((_, c)) = chars.next_if(|&(i, _)| i < offset) {
+                if c == '\n' {
+                    line += 1;
+                    column = 1;
+                } else {
+                    column += 1;
+                }
+            }
+            (
+                ins,
+                Span {
+                    offset,
+                    line,
+                    column,
+                },
+            )
+        })
+        .collect();
+
+    Ok((rest, spanned))
+}
+
+// Anything that isn't an instruction is skipped, except that comments are still
+// skipped as a whole so hands inside them don't run.
+pub fn parse_hand_code_lenient(input: &str) -> IResult<&str, Vec<Instruction>> {
+    let token = alt((
+        value(None, comment),
+        map(instruction, Some),
+        value(None, anychar),
+    ));
+    let push = |mut instructions: Vec<_>, token| {
+        instructions.extend(token);
+        instructions
+    };
+    fold_many0(token, Vec::new, push)(input)
+}
+
+fn instruction(input: &str) -> IResult<&str, Instruction> {
+    dialect_instruction(Dialect::default())(input)
+}
+
+fn dialect_instruction<'a>(
+    dialect: Dialect,
+) -> impl FnMut(&'a str) -> IResult<&'a str, Instruction> {
+    terminated(
+        map_opt(anychar, move |c| dialect.instruction(c)),
+        many0_count(satisfy(is_modifier)),
+    )
+}
+
+// Keyboards often attach a variation selector (U+FE0F) or a skin tone
+// (U+1F3FB to U+1F3FF) to the hands, they don't change the instruction.
+pub(crate) fn is_modifier(c: char) -> bool {
+    matches!(c, '\u{FE0F}' | '\u{1F3FB}'..='\u{1F3FF}')
+}
+
+// A comment runs from # to the end of the line.
+fn comment(input: &str) -> IResult<&str, &str> {
+    recognize(pair(char('#'), opt(is_not("\r\n"))))(input)
+}
+
+fn trivia(input: &str) -> IResult<&str, ()> {
+    value((), many0_count(alt((multispace1, comment))))(input)
+}
+
+pub fn parse_brainfuck(input: &str) -> IResult<&str, Vec<Instruction>> {
+    use Instruction::*;
+    let ins = |c, v| value(Some(v), char(c));
+    let command = alt((
+        ins('>', Next),
+        ins('<', Previous),
+        ins('+', Increment),
+        ins('-', Decrease),
+        ins('[', LoopStart),
+        ins(']', LoopEnd),
+        ins('.', Print),
+        ins(',', Input),
+        value(None, anychar),
+    ));
+    let (rest, commands) = many0(command)(input)?;
+    Ok((rest, commands.into_iter().flatten().collect()))
+}
+
+#[test]
+pub fn test_parse_error() {
+    let result = crate::interpret("👆👆hello", std::io::empty(), std::io::sink());
+
+    match result {
+        Err(HandError::Parse { offset, message }) => {
+            assert_eq!(offset, 8);
+            assert_eq!(message, "unexpected character 'h'");
+        }
+        _ => panic!("expected a parse error"),
+    }
+}
+
+#[test]
+pub fn test_brainfuck_hello_world() -> Result<(), HandError> {
+    let code = "Hello World! ++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.
+        print it: >---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+    let (rest, instructions) = parse_brainfuck(code).unwrap();
+    let mut output = Vec::new();
+
+    crate::run_hand_ast(std::io::empty(), &mut output, &instructions)?;
+
+    assert_eq!(rest, "");
+    assert_eq!(String::from_utf8(output).unwrap(), "Hello World!\n");
+
+    Ok(())
+}
+
+#[test]
+pub fn test_brainfuck_round_trip() {
+    let code = include_str!("../../test2.hand").trim();
+    let (_, instructions) = parse_hand_code(code).unwrap();
+
+    let (_, imported) = parse_brainfuck(&crate::to_brainfuck(&instructions)).unwrap();
+
+    assert_eq!(imported, instructions);
+}
+
+#[test]
+pub fn test_comments() {
+    let commented = "# prints the letter A
+        👆👆👆👆👆👆👆👆 # eight
+        🤜👇👉👆👆👆👆👆👆👆👆👈🤛 # times eight
+        👉👆👊 # plus one, then print
+        #";
+    let plain = "👆👆👆👆👆👆👆👆🤜👇👉👆👆👆👆👆👆👆👆👈🤛👉👆👊";
+
+    let (_, with_comments) = parse_hand_code(commented).unwrap();
+    let (_, without_comments) = parse_hand_code(plain).unwrap();
+
+    assert_eq!(with_comments, without_comments);
+}
+
+#[test]
+pub fn test_lenient_parse_skips_unknown_characters() {
+    let noisy = "Hello: 👇🤜👇👇👇👇👇👇👇👉👆👈🤛
+        then 👉👇👊 and # not 👊 this one
+        done!";
+    let clean = "👇🤜👇👇👇👇👇👇👇👉👆👈🤛👉👇👊";
+
+    let (rest, lenient) = parse_hand_code_lenient(noisy).unwrap();
+    let (_, strict) = parse_hand_code(clean).unwrap();
+
+    assert_eq!(rest, "");
+    assert_eq!(lenient, strict);
+    assert!(parse_hand_code(noisy).is_err());
+}
+
+#[test]
+pub fn test_spans() {
+    let code = "👆 # first\n  👉 👊\n👇";
+
+    let (_, spanned) = parse_hand_code_spanned(code).unwrap();
+
+    let (_, plain) = parse_hand_code(code).unwrap();
+    let instructions: Vec<_> = spanned.iter().map(|&(ins, _)| ins).collect();
+    assert_eq!(instructions, plain);
+    assert_eq!(
+        spanned[2],
+        (
+            Instruction::Print,
+            Span {
+                offset: 20,
+                line: 2,
+                column: 5
+            }
+        )
+    );
+    assert_eq!(&code[spanned[2].1.offset..], "👊\n👇");
+    assert_eq!(spanned[3].1.line, 3);
+    assert_eq!(spanned[3].1.column, 1);
+}
+
+#[test]
+pub fn test_modifiers() {
+    let base = "👉👈👆👇🤜🤛👊✋";
+    let (_, expected) = parse_hand_code(base).unwrap();
+
+    let with_selector: String = base.chars().flat_map(|c| [c, '\u{FE0F}']).collect();
+    let (_, instructions) = parse_hand_code(&with_selector).unwrap();
+    assert_eq!(instructions, expected);
+
+    for tone in '\u{1F3FB}'..='\u{1F3FF}' {
+        let toned: String = base.chars().flat_map(|c| [c, tone]).collect();
+        let (_, instructions) = parse_hand_code(&toned).unwrap();
+        assert_eq!(instructions, expected);
+    }
+
+    let (_, instructions) = parse_hand_code("👊🏽 👊\u{FE0F} 👊").unwrap();
+    assert_eq!(instructions, vec![Instruction::Print; 3]);
+}
+
+#[test]
+pub fn test_arbitrary_input_never_panics() {
+    let alphabet = [
+        "👉", "👈", "👆", "👇", "🤜", "🤛", "👊", "✋", "\u{FE0F}", "🏽", "#", "\n", "\r", " ",
+        "a", "👋", "+", "[", "]", "\u{0}",
+    ];
+    let mut seed = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        seed
+    };
+    let config = crate::InterpreterConfig {
+        max_steps: Some(1000),
+        ..crate::InterpreterConfig::default()
+    };
+
+    for _ in 0..2000 {
+        let len = next() % 40;
+        let source: String = (0..len)
+            .map(|_| alphabet[(next() % alphabet.len() as u64) as usize])
+            .collect();
+        let bytes: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+
+        let _ = parse_program_bytes(&bytes);
+        let _ = parse_hand_code_lenient(&source);
+        let _ = parse_hand_code_spanned(&source);
+        let _ = parse_brainfuck(&source);
+        let _ = crate::lint(&source);
+        if let Ok(instructions) = parse_program(&source) {
+            let _ = crate::calc_wormholes(&instructions);
+            let _ = crate::translate(&instructions);
+            let _ = crate::eliminate_dead_code(&instructions, &config);
+            let _ = crate::run_hand_ast_with_config(
+                &config,
+                std::io::empty(),
+                std::io::sink(),
+                &instructions,
+            );
+        }
+    }
+}
+
+#[test]
+pub fn test_deeply_nested_loops() {
+    let depth = 100_000;
+    let source = format!("{}{}", "🤜".repeat(depth), "🤛".repeat(depth - 1));
+
+    let instructions = parse_program(&source).unwrap();
+
+    assert_eq!(instructions.len(), 2 * depth - 1);
+    assert!(matches!(
+        crate::compile(&instructions),
+        Err(HandError::UnmatchedLoopStart { offset: 0 })
+    ));
+    assert!(crate::calc_wormholes(&instructions).is_err());
+    assert!(matches!(
+        parse_program_bytes(b"\xf0\x9f\x91\x86\xff"),
+        Err(HandError::InvalidEncoding { offset: 4 })
+    ));
+}
+
+#[test]
+pub fn test_invalid_encoding() {
+    let mut source = "👆👊 # a comment with ".as_bytes().to_vec();
+    let bad = source.len();
+    source.extend_from_slice(b"\xe2\x28\xa1 and more");
+
+    let result = parse_program_bytes(&source);
+
+    let error = result.unwrap_err();
+    assert!(matches!(error, HandError::InvalidEncoding { offset } if offset == bad));
+    assert_eq!(
+        error.to_string(),
+        format!("program is not valid UTF-8 at byte {}", bad)
+    );
+    assert_eq!(
+        parse_program_bytes("👆👊 # fine".as_bytes()).unwrap(),
+        vec![Instruction::Increment, Instruction::Print]
+    );
+    assert!(matches!(
+        parse_program_bytes(b"\xf0\x9f\x91"),
+        Err(HandError::InvalidEncoding { offset: 0 })
+    ));
+}
+
+#[test]
+pub fn test_custom_dialect() -> Result<(), HandError> {
+    let ascii = Dialect {
+        next: '>',
+        previous: '<',
+        increment: '+',
+        decrease: '-',
+        loop_start: '[',
+        loop_end: ']',
+        print: '.',
+        input: ',',
+    };
+    let code = include_str!("../../test2.hand").trim();
+    let hands = parse_program(code)?;
+    let source = format!("# hello world\n{}\n", crate::to_brainfuck(&hands));
+
+    let instructions = parse_program_with(&source, ascii)?;
+
+    assert_eq!(instructions, hands);
+    let run = |instructions: &[Instruction]| -> Result<Vec<u8>, HandError> {
+        let mut output = Vec::new();
+        crate::run_hand_ast(std::io::empty(), &mut output, instructions)?;
+        Ok(output)
+    };
+    assert_eq!(run(&instructions)?, run(&hands)?);
+    assert_eq!(run(&instructions)?, b"Hello World!\n");
+
+    assert_eq!(ascii.instruction('['), Some(Instruction::LoopStart));
+    assert_eq!(ascii.instruction('👉'), None);
+    assert!(matches!(
+        parse_program_with("+ 👆", ascii),
+        Err(HandError::Parse { offset: 2, .. })
+    ));
+    assert_eq!(
+        Dialect::default().instruction('✋'),
+        Some(Instruction::Input)
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn test_empty_programs() -> Result<(), HandError> {
+    for source in [
+        "",
+        " ",
+        "\n\t \r\n",
+        "# just a comment",
+        "  # two\n# comments\n",
+    ] {
+        assert_eq!(parse_hand_code(source), Ok(("", vec![])));
+        assert_eq!(parse_program(source)?, vec![]);
+        assert_eq!(parse_hand_code_spanned(source), Ok(("", vec![])));
+        assert_eq!(parse_hand_code_lenient(source), Ok(("", vec![])));
+
+        assert_eq!(crate::eval_to_bytes(source)?, b"");
+        let state = crate::run_hand_ast_with_state(
+            &crate::InterpreterConfig::default(),
+            std::io::empty(),
+            std::io::sink(),
+            &parse_program(source)?,
+        )?;
+        assert_eq!((state.buffer(), state.cursor, state.steps), (vec![0], 0, 0));
+    }
+    assert!(crate::compile(&[])?.ops.is_empty());
+
+    Ok(())
+}
+
+#[test]
+pub fn test_parse_balanced() -> Result<(), HandError> {
+    let code = include_str!("../../test2.hand");
+    assert_eq!(parse_program_balanced(code)?, parse_program(code)?);
+    assert!(parse_program_balanced("")?.is_empty());
+
+    // the stray 🤛 is found before anything after it is read
+    for (source, offset) in [
+        ("🤛", 0),
+        ("👆👆 🤛🤜", 9),
+        ("🤜🤛 # fine\n🤛 hello", 16),
+        ("🤜👇🤛🤛🤜🤜", 12),
+    ] {
+        match parse_program_balanced(source) {
+            Err(HandError::Parse {
+                offset: found,
+                message,
+            }) => {
+                assert_eq!(found, offset, "{}", source);
+                assert_eq!(message, "🤛 has no matching 🤜");
+            }
+            other => panic!("expected a parse error for {}, got {:?}", source, other),
+        }
+    }
+    // where matching afterwards only gives the instruction
+    let instructions = parse_program("👆👆 🤛🤜")?;
+    assert!(matches!(
+        crate::calc_wormholes(&instructions),
+        Err(HandError::UnmatchedLoopEnd { offset: 2 })
+    ));
+
+    // the innermost 🤜 left open
+    assert!(matches!(
+        parse_program_balanced("🤜🤜👆🤛 🤜👇"),
+        Err(HandError::Parse { offset: 17, .. })
+    ));
+    // other errors are the same as from parse_program
+    assert!(matches!(
+        parse_program_balanced("🤜👆x🤛"),
+        Err(HandError::Parse { offset: 8, .. })
+    ));
+    Ok(())
+}
+
+#[test]
+pub fn test_input_directive() -> Result<(), HandError> {
+    let source = "!input: hello\n# echoes its input\n✋🤜👊✋🤛";
+    let (instructions, input) = parse_program_with_input(source)?;
+    assert_eq!(instructions, parse_program("✋🤜👊✋🤛")?);
+    assert_eq!(input.as_deref(), Some("hello"));
+    let mut output = Vec::new();
+    crate::run_hand_ast(input.unwrap().as_bytes(), &mut output, &instructions)?;
+    assert_eq!(output, b"hello");
+
+    let (_, input) = parse_program_with_input("!input:two\\nlines\\\\\r\n👊")?;
+    assert_eq!(input.as_deref(), Some("two\nlines\\"));
+    let (instructions, input) = parse_program_with_input("!input:")?;
+    assert!(instructions.is_empty());
+    assert_eq!(input.as_deref(), Some(""));
+    let (instructions, input) = parse_program_with_input("👆👊")?;
+    assert_eq!((instructions.len(), input), (2, None));
+
+    // only the first line can be a directive, and offsets count it
+    assert!(matches!(
+        parse_program_with_input("!input: a\n👆x"),
+        Err(HandError::Parse { offset: 14, .. })
+    ));
+    assert!(matches!(
+        parse_program_with_input("👆\n!input: a"),
+        Err(HandError::Parse { offset: 5, .. })
+    ));
+    assert!(parse_program(source).is_err());
+    Ok(())
+}
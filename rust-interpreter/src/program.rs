@@ -0,0 +1,211 @@
+use crate::interpreter::move_error;
+use crate::io::{Read, Write};
+use crate::{
+    compile, parse_program, Bytecode, Cell, HandError, Instruction, Interpreter, InterpreterConfig,
+};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+// A parsed and compiled program with its loop jumps already resolved, ready to
+// run any number of times.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Program {
+    instructions: Vec<Instruction>,
+    bytecode: Bytecode,
+}
+
+impl Program {
+    pub fn compile(source: &str) -> Result<Self, HandError> {
+        Self::from_instructions(parse_program(source)?)
+    }
+
+    pub fn from_instructions(instructions: Vec<Instruction>) -> Result<Self, HandError> {
+        let bytecode = compile(&instructions)?;
+        Ok(Program {
+            instructions,
+            bytecode,
+        })
+    }
+
+    pub fn instructions(&self) -> &[Instruction] {
+        &self.instructions
+    }
+
+    pub fn bytecode(&self) -> &Bytecode {
+        &self.bytecode
+    }
+
+    // The bytecode as text, see Bytecode::disassemble.
+    pub fn disassemble(&self) -> String {
+        self.bytecode.disassemble()
+    }
+
+    pub fn run(&self, reader: impl Read, writer: impl Write) -> Result<(), HandError> {
+        self.run_with_config(&InterpreterConfig::default(), reader, writer)
+    }
+
+    pub fn run_with_config(
+        &self,
+        config: &InterpreterConfig,
+        reader: impl Read,
+        writer: impl Write,
+    ) -> Result<(), HandError> {
+        crate::validate(&self.instructions, config)?;
+        Interpreter::with_config(config, &self.bytecode, reader, writer).run()
+    }
+
+    // Runs with the tape starting out as the given cells from cell 0 on and the
+    // pointer at cursor, such as the cells another program left behind. Cells
+    // past the given ones hold 0, a cursor left of cell 0 fails with
+    // PointerUnderflow at instruction 0.
+    pub fn run_with_tape(
+        &self,
+        tape: &[Cell],
+        cursor: isize,
+        reader: impl Read,
+        writer: impl Write,
+    ) -> Result<(), HandError> {
+        let config = InterpreterConfig::default();
+        crate::validate(&self.instructions, &config)?;
+        let mut interpreter = Interpreter::with_config(&config, &self.bytecode, reader, writer);
+        let cells = (0..).zip(tape.iter().copied());
+        interpreter
+            .load_tape(cells, 0..tape.len() as isize, cursor)
+            .map_err(|e| move_error(e, 0))?;
+        interpreter.run()
+    }
+}
+
+// Runs a copy of the program on a new thread with the given input, the handle
+// gives back everything it printed. Set max_steps or max_duration in the config
+// to bound how long the thread can take.
+#[cfg(feature = "std")]
+pub fn run_on_thread(
+    program: &Program,
+    config: &InterpreterConfig,
+    input: Vec<u8>,
+) -> std::thread::JoinHandle<Result<Vec<u8>, HandError>> {
+    let program = program.clone();
+    let config = config.clone();
+    std::thread::spawn(move || {
+        let mut output = Vec::new();
+        program.run_with_config(&config, &input[..], &mut output)?;
+        Ok(output)
+    })
+}
+
+#[test]
+pub fn test_run_program_twice() -> Result<(), HandError> {
+    let program = Program::compile("✋🤜👊✋🤛")?;
+
+    let mut first = Vec::new();
+    program.run(&b"first"[..], &mut first)?;
+    let mut second = Vec::new();
+    program.run(&b"second run"[..], &mut second)?;
+
+    assert_eq!(first, b"first");
+    assert_eq!(second, b"second run");
+    assert_eq!(program.instructions().len(), 5);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_run_with_tape() -> Result<(), HandError> {
+    let print_three = Program::compile("👊👉👊👉👊")?;
+
+    let mut output = Vec::new();
+    print_three.run_with_tape(&[72, 105, 33], 0, crate::io::empty(), &mut output)?;
+    assert_eq!(output, b"Hi!");
+
+    // the pointer starts where it was left, cells past the tape are 0
+    let mut output = Vec::new();
+    print_three.run_with_tape(&[72, 105, 33], 1, crate::io::empty(), &mut output)?;
+    assert_eq!(output, b"i!\0");
+    let mut output = Vec::new();
+    print_three.run_with_tape(&[], 0, crate::io::empty(), &mut output)?;
+    assert_eq!(output, [0, 0, 0]);
+
+    // chaining one program into another
+    let program = Program::from_instructions(parse_program("👆👆👆🤜👉👆👆👈👇🤛")?)?;
+    let mut interpreter = Interpreter::with_config(
+        &InterpreterConfig::default(),
+        program.bytecode(),
+        [].as_slice(),
+        Vec::new(),
+    );
+    interpreter.run()?;
+    let state = interpreter.snapshot();
+    let buffer = interpreter.buffer();
+    let mut output = Vec::new();
+    Program::compile("👊")?.run_with_tape(
+        &buffer,
+        state.cursor,
+        crate::io::empty(),
+        &mut output,
+    )?;
+    assert_eq!(output, [0]);
+    let mut output = Vec::new();
+    Program::compile("👉👊")?.run_with_tape(
+        &buffer,
+        state.cursor,
+        crate::io::empty(),
+        &mut output,
+    )?;
+    assert_eq!(output, [6]);
+
+    let result = print_three.run_with_tape(&[1], -1, crate::io::empty(), Vec::new());
+    assert!(matches!(
+        result,
+        Err(HandError::PointerUnderflow { offset: 0 })
+    ));
+    Ok(())
+}
+
+#[test]
+pub fn test_run_on_threads() -> Result<(), HandError> {
+    fn assert_send<T: Send>() {}
+    fn assert_sync<T: Sync>() {}
+    assert_send::<Program>();
+    assert_sync::<Program>();
+    assert_send::<InterpreterConfig>();
+    assert_sync::<InterpreterConfig>();
+    assert_send::<Interpreter<'static, std::io::Empty, Vec<u8>>>();
+    assert_send::<HandError>();
+
+    let hello = Program::compile(include_str!("../../test1.hand").trim())?;
+    let hello_world = Program::compile(include_str!("../../test2.hand").trim())?;
+    let echo = Program::compile("✋🤜👊✋🤛")?;
+    let forever = Program::compile("👆🤜🤛")?;
+    let config = InterpreterConfig {
+        max_steps: Some(100_000),
+        ..InterpreterConfig::default()
+    };
+
+    let handles: Vec<_> = (0..4)
+        .flat_map(|i| {
+            [
+                run_on_thread(&hello, &config, Vec::new()),
+                run_on_thread(&hello_world, &config, Vec::new()),
+                run_on_thread(&echo, &config, format!("thread {}", i).into_bytes()),
+                run_on_thread(&forever, &config, Vec::new()),
+            ]
+        })
+        .collect();
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+    for (i, chunk) in results.chunks(4).enumerate() {
+        assert_eq!(chunk[0].as_ref().unwrap(), b"Hello");
+        assert_eq!(chunk[1].as_ref().unwrap(), b"Hello World!\n");
+        assert_eq!(
+            chunk[2].as_ref().unwrap(),
+            format!("thread {}", i).as_bytes()
+        );
+        assert!(matches!(
+            chunk[3],
+            Err(HandError::StepLimitExceeded { limit: 100_000, .. })
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+use crate::io::{Read, Write};
+use crate::tape::new_tape;
+use crate::{
+    compile, parse_program, validate, HandError, Interpreter, InterpreterConfig, MemoryView, Tape,
+};
+use alloc::boxed::Box;
+
+// Runs one line of code at a time against a tape that outlives each line, so
+// cells and the pointer carry over. Every line has to balance its own loops.
+pub struct Repl {
+    config: InterpreterConfig,
+    tape: Box<dyn Tape>,
+}
+
+impl Repl {
+    pub fn new(config: &InterpreterConfig) -> Self {
+        Repl {
+            config: config.clone(),
+            tape: new_tape(config),
+        }
+    }
+
+    // Cells changed before an error stay changed.
+    pub fn eval(
+        &mut self,
+        line: &str,
+        reader: impl Read,
+        writer: impl Write,
+    ) -> Result<(), HandError> {
+        let instructions = parse_program(line)?;
+        validate(&instructions, &self.config)?;
+        let bytecode = compile(&instructions)?;
+        Interpreter::with_tape(&self.config, bytecode, &mut *self.tape, reader, writer).run()
+    }
+
+    pub fn memory(&self) -> MemoryView<'_> {
+        MemoryView::of(self.tape.as_ref(), &self.config)
+    }
+}
+
+#[test]
+pub fn test_repl_keeps_state() -> Result<(), HandError> {
+    let mut repl = Repl::new(&InterpreterConfig::default());
+    let mut output = Vec::new();
+
+    repl.eval("👆👆", std::io::empty(), &mut output)?;
+    assert_eq!(*repl.memory().cells, [2]);
+    repl.eval("👊👉👆", std::io::empty(), &mut output)?;
+    assert_eq!(output, vec![2]);
+    assert_eq!(repl.memory().to_string(), "2 [1]");
+
+    assert!(repl.eval("🤜", std::io::empty(), &mut output).is_err());
+    assert!(repl.eval("👈👈", std::io::empty(), &mut output).is_err());
+    repl.eval("👈👆", std::io::empty(), &mut output)?;
+    assert_eq!(repl.memory().to_string(), "[3] 1");
+
+    Ok(())
+}
@@ -0,0 +1,302 @@
+use crate::interpreter::read_byte;
+use crate::io::{Read, Write};
+use crate::parser::is_modifier;
+use crate::{Dialect, HandError, Instruction, Interpreter, InterpreterConfig};
+use alloc::format;
+
+// How many instructions run_stream collects outside of any loop before running
+// them. Loops are always collected whole, however long they are.
+const BATCH: usize = 256;
+
+// Parses a program one instruction at a time as it reads the source, the same
+// way parse_program does, without holding the whole program. The source is read
+// one byte at a time, so a file is best wrapped in a BufReader first. Nothing
+// more is read after the first error.
+pub struct HandStream<R: Read> {
+    source: R,
+    dialect: Dialect,
+    offset: usize,           // bytes of the source read so far
+    peeked: Option<char>,    // a character read past the end of the last instruction
+    after_instruction: bool, // modifiers are only allowed right after an instruction
+    failed: bool,
+}
+
+impl<R: Read> HandStream<R> {
+    pub fn new(source: R) -> Self {
+        Self::with_dialect(source, Dialect::default())
+    }
+
+    pub fn with_dialect(source: R, dialect: Dialect) -> Self {
+        HandStream {
+            source,
+            dialect,
+            offset: 0,
+            peeked: None,
+            after_instruction: false,
+            failed: false,
+        }
+    }
+
+    // Byte offset in the source of the next character to look at.
+    pub fn offset(&self) -> usize {
+        self.offset - self.peeked.map_or(0, char::len_utf8)
+    }
+
+    fn next_char(&mut self) -> Result<Option<char>, HandError> {
+        if let Some(c) = self.peeked.take() {
+            return Ok(Some(c));
+        }
+        let start = self.offset;
+        let Some(first) = self.next_byte()? else {
+            return Ok(None);
+        };
+        let len = match first {
+            0x00..=0x7F => 1,
+            0xC2..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF4 => 4,
+            _ => return Err(HandError::InvalidEncoding { offset: start }),
+        };
+        let mut bytes = [first, 0, 0, 0];
+        for byte in &mut bytes[1..len] {
+            *byte = self
+                .next_byte()?
+                .ok_or(HandError::InvalidEncoding { offset: start })?;
+        }
+        let c = core::str::from_utf8(&bytes[..len])
+            .map_err(|_| HandError::InvalidEncoding { offset: start })?;
+        Ok(c.chars().next())
+    }
+
+    fn next_byte(&mut self) -> Result<Option<u8>, HandError> {
+        let byte = read_byte(&mut self.source)?;
+        self.offset += byte.is_some() as usize;
+        Ok(byte)
+    }
+
+    fn next_instruction(&mut self) -> Result<Option<Instruction>, HandError> {
+        while let Some(c) = self.next_char()? {
+            let after_instruction = core::mem::take(&mut self.after_instruction);
+            match c {
+                ' ' | '\t' | '\r' | '\n' => {}
+                '#' => loop {
+                    match self.next_char()? {
+                        Some(c @ ('\r' | '\n')) => {
+                            self.peeked = Some(c);
+                            break;
+                        }
+                        Some(_) => {}
+                        None => return Ok(None),
+                    }
+                },
+                _ if after_instruction && is_modifier(c) => self.after_instruction = true,
+                _ => match self.dialect.instruction(c) {
+                    Some(ins) => {
+                        self.after_instruction = true;
+                        return Ok(Some(ins));
+                    }
+                    None => {
+                        let offset = self.offset - c.len_utf8();
+                        let message = format!("unexpected character {:?}", c);
+                        return Err(HandError::Parse { offset, message });
+                    }
+                },
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: Read> Iterator for HandStream<R> {
+    type Item = Result<Instruction, HandError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let next = self.next_instruction().transpose();
+        self.failed = matches!(next, Some(Err(_)));
+        next
+    }
+}
+
+// Runs a program as its instructions come, such as from a HandStream, matching
+// loops as it goes. Instructions are kept only until the loop they are in has
+// been closed and run, so a long program without deep loops runs in little
+// memory. Errors in the program show up once the instructions before them
+// have run: a parse error stops the run there, and an unmatched loop fails
+// when it is reached.
+pub fn run_stream(
+    config: &InterpreterConfig,
+    program: impl IntoIterator<Item = Result<Instruction, HandError>>,
+    reader: impl Read,
+    writer: impl Write,
+) -> Result<(), HandError> {
+    let mut interpreter = Interpreter::with_lazy_jumps(config, &[], reader, writer);
+    let result = feed(&mut interpreter, program.into_iter());
+    let flushed = interpreter.flush_at_end().map_err(HandError::from);
+    result.and(flushed)
+}
+
+fn feed<R: Read, W: Write>(
+    interpreter: &mut Interpreter<'_, R, W>,
+    mut program: impl Iterator<Item = Result<Instruction, HandError>>,
+) -> Result<(), HandError> {
+    let mut offset = 0;
+    let mut depth = 0usize;
+    let mut pending = 0;
+    loop {
+        let ins = match program.next().transpose() {
+            Ok(ins) => ins,
+            Err(e) => {
+                interpreter.run_ops()?;
+                return Err(e);
+            }
+        };
+        if let Some(ins) = ins {
+            interpreter.push_unresolved(ins, offset);
+            offset += 1;
+            pending += 1;
+            match ins {
+                Instruction::LoopStart => depth += 1,
+                Instruction::LoopEnd => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        if ins.is_none() || (depth == 0 && pending >= BATCH) {
+            if !interpreter.run_ops()? {
+                return Ok(());
+            }
+            interpreter.drop_finished();
+            pending = 0;
+        }
+        if ins.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+#[test]
+pub fn test_stream_matches_parser() {
+    let samples = [
+        include_str!("../../test1.hand"),
+        include_str!("../../test2.hand"),
+        "# prints the letter A\n👆👆 # two\r\n🤜👇👉👆👆👈🤛 # loop\n👉👊",
+        "👆\u{FE0F}👆🏽👆🏻\u{FE0F}👊 ✋🏿",
+        "",
+        "  # only a comment",
+    ];
+    for sample in samples {
+        let (_, expected) = crate::parse_hand_code(sample).unwrap();
+        let streamed: Result<alloc::vec::Vec<_>, _> = HandStream::new(sample.as_bytes()).collect();
+        assert_eq!(streamed.unwrap(), expected);
+    }
+
+    let dialect = Dialect {
+        next: '>',
+        previous: '<',
+        increment: '+',
+        decrease: '-',
+        loop_start: '[',
+        loop_end: ']',
+        print: '.',
+        input: ',',
+    };
+    let source = "++[->+<] # add\n>.";
+    let expected = crate::parse_program_with(source, dialect).unwrap();
+    let streamed: Result<alloc::vec::Vec<_>, _> =
+        HandStream::with_dialect(source.as_bytes(), dialect).collect();
+    assert_eq!(streamed.unwrap(), expected);
+}
+
+#[test]
+pub fn test_stream_errors() {
+    let mut stream = HandStream::new("👆 x👆".as_bytes());
+    assert_eq!(stream.next().unwrap().unwrap(), Instruction::Increment);
+    assert!(matches!(
+        stream.next(),
+        Some(Err(HandError::Parse { offset: 5, .. }))
+    ));
+    assert!(stream.next().is_none());
+
+    // a modifier has to follow a hand
+    let mut stream = HandStream::new("👆 \u{FE0F}".as_bytes());
+    stream.next();
+    assert!(matches!(
+        stream.next(),
+        Some(Err(HandError::Parse { offset: 5, .. }))
+    ));
+
+    let mut source = "👆👊".as_bytes().to_vec();
+    source.push(0xFF);
+    let mut stream = HandStream::new(&source[..]);
+    assert_eq!(stream.nth(1).unwrap().unwrap(), Instruction::Print);
+    assert!(matches!(
+        stream.next(),
+        Some(Err(HandError::InvalidEncoding { offset: 8 }))
+    ));
+    // the end of the source in the middle of a hand
+    let truncated = &"👆".as_bytes()[..3];
+    assert!(matches!(
+        HandStream::new(truncated).next(),
+        Some(Err(HandError::InvalidEncoding { offset: 0 }))
+    ));
+}
+
+#[test]
+pub fn test_run_stream() -> Result<(), HandError> {
+    let config = InterpreterConfig::default();
+    for sample in [
+        include_str!("../../test1.hand"),
+        include_str!("../../test2.hand"),
+    ] {
+        let (_, instructions) = crate::parse_hand_code(sample).unwrap();
+        let mut expected = alloc::vec::Vec::new();
+        crate::run_hand_ast(crate::io::empty(), &mut expected, &instructions)?;
+        let mut output = alloc::vec::Vec::new();
+        run_stream(
+            &config,
+            HandStream::new(sample.as_bytes()),
+            crate::io::empty(),
+            &mut output,
+        )?;
+        assert_eq!(output, expected);
+    }
+
+    // long enough to be run in several batches, one of them ending in a loop
+    let source = alloc::format!("{}🤜👉👆👈👇🤛👉👊", "👆".repeat(300));
+    let mut output = alloc::vec::Vec::new();
+    run_stream(
+        &config,
+        HandStream::new(source.as_bytes()),
+        crate::io::empty(),
+        &mut output,
+    )?;
+    assert_eq!(output, [44]);
+
+    // what comes before a parse error still runs
+    let mut output = alloc::vec::Vec::new();
+    let result = run_stream(
+        &config,
+        HandStream::new("👆👊x👊".as_bytes()),
+        crate::io::empty(),
+        &mut output,
+    );
+    assert!(matches!(result, Err(HandError::Parse { offset: 8, .. })));
+    assert_eq!(output, [1]);
+
+    // an unmatched loop only fails once it has to jump
+    let mut output = alloc::vec::Vec::new();
+    let result = run_stream(
+        &config,
+        HandStream::new("👆👊👇🤜👊".as_bytes()),
+        crate::io::empty(),
+        &mut output,
+    );
+    assert!(matches!(
+        result,
+        Err(HandError::UnmatchedLoopStart { offset: 3 })
+    ));
+    assert_eq!(output, [1]);
+    Ok(())
+}
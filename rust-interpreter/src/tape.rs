@@ -0,0 +1,682 @@
+use crate::{BoundaryPolicy, Cell, InterpreterConfig, TapeKind};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::vec;
+use alloc::vec::Vec;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TapeError {
+    Underflow { moved: usize },              // moved left of the first cell
+    Overflow { limit: usize, moved: usize }, // grew past the configured max_tape_size
+    OutOfRange { index: isize },             // no such cell, only from IndexPolicy::Error
+}
+// In the first two cases moved counts the single cell moves that worked before
+// the one that failed, the pointer itself is left where it was.
+
+// What reading or writing a cell by its absolute position does with a position
+// outside the cells the tape holds, from first_cell for cell_count cells.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IndexPolicy {
+    Error, // fails with OutOfRange and the tape stays as it is
+    Grow,  // grows the tape up to the position, as far as the boundary allows
+    Wrap,  // counts the position around the cells there are
+}
+
+// Cell storage together with the memory pointer. Tapes are Send so that an
+// interpreter can move to another thread with its tape.
+pub trait Tape: Send {
+    fn get(&self) -> Cell; // value of the cell at the pointer
+    fn set(&mut self, value: Cell);
+    fn move_left(&mut self, n: usize) -> Result<(), TapeError>;
+    fn move_right(&mut self, n: usize) -> Result<(), TapeError>;
+    fn cursor(&self) -> isize; // position of the pointer, negative once the tape grew left
+    fn cells(&self) -> Cow<'_, [Cell]>; // every cell from first_cell on, see touched for fewer
+
+    // Position of the first cell returned by cells.
+    fn first_cell(&self) -> isize {
+        0
+    }
+
+    // How many cells cells returns.
+    fn cell_count(&self) -> usize {
+        self.cells().len()
+    }
+
+    // Position and value of every cell the tape holds, in order. A tape that
+    // only stores cells something was written to leaves the others out, which
+    // then hold the initial cell value, so this can be far less than cells.
+    fn touched(&self) -> Vec<(isize, Cell)> {
+        let first = self.first_cell();
+        let cells = self.cells();
+        (first..).zip(cells.iter().copied()).collect()
+    }
+
+    // Moves step cells at a time until the cell at the pointer is 0, but no more
+    // than max_moves times, and returns how many moves it made. Tapes where the
+    // cell may never be 0 can give up at any nonzero cell, the interpreter calls
+    // this again on its next step. By default the seek gives up at the first
+    // cell past the ones the tape spanned, which are all blank.
+    fn seek_zero(&mut self, step: isize, max_moves: usize) -> Result<usize, TapeError> {
+        step_to_zero(self, step, max_moves)
+    }
+}
+
+fn step_to_zero<T: Tape + ?Sized>(
+    tape: &mut T,
+    step: isize,
+    max_moves: usize,
+) -> Result<usize, TapeError> {
+    let first = tape.first_cell();
+    let span = first..first + tape.cell_count() as isize;
+    let mut moves = 0;
+    while tape.get() != 0 && moves < max_moves {
+        if step < 0 {
+            tape.move_left(step.unsigned_abs())?;
+        } else {
+            tape.move_right(step as usize)?;
+        }
+        moves += 1;
+        // a blank cell that isn't 0 would keep the seek going forever, out of
+        // reach of max_steps unless the interpreter gets a step in between
+        if !span.contains(&tape.cursor()) {
+            break;
+        }
+    }
+    Ok(moves)
+}
+
+impl<T: Tape + ?Sized> Tape for &mut T {
+    fn get(&self) -> Cell {
+        (**self).get()
+    }
+
+    fn set(&mut self, value: Cell) {
+        (**self).set(value)
+    }
+
+    fn move_left(&mut self, n: usize) -> Result<(), TapeError> {
+        (**self).move_left(n)
+    }
+
+    fn move_right(&mut self, n: usize) -> Result<(), TapeError> {
+        (**self).move_right(n)
+    }
+
+    fn cursor(&self) -> isize {
+        (**self).cursor()
+    }
+
+    fn cells(&self) -> Cow<'_, [Cell]> {
+        (**self).cells()
+    }
+
+    fn first_cell(&self) -> isize {
+        (**self).first_cell()
+    }
+
+    fn cell_count(&self) -> usize {
+        (**self).cell_count()
+    }
+
+    fn touched(&self) -> Vec<(isize, Cell)> {
+        (**self).touched()
+    }
+
+    fn seek_zero(&mut self, step: isize, max_moves: usize) -> Result<usize, TapeError> {
+        (**self).seek_zero(step, max_moves)
+    }
+}
+
+// The value of a cell nothing has been written to, cut down to the cell width.
+pub(crate) fn initial_cell(config: &InterpreterConfig) -> Cell {
+    config.initial_cell_value & config.cell_width.max_value()
+}
+
+pub(crate) fn new_tape(config: &InterpreterConfig) -> Box<dyn Tape> {
+    match config.tape {
+        _ if config.boundary == BoundaryPolicy::Wrap => Box::new(WrappingTape::new(config)),
+        _ if config.boundary == BoundaryPolicy::Clamp => Box::new(ClampedTape::new(config)),
+        TapeKind::Contiguous => Box::new(VecTape::new(config)),
+        TapeKind::Sparse => Box::new(SparseTape::new(config)),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VecTape {
+    cells: Vec<Cell>,
+    start: usize,  // cells before this index are spare room for growing left
+    origin: usize, // index of the cell at position 0
+    cursor: usize, // index of the cell at the pointer
+    limit: Option<usize>,
+    grow_left: bool,
+    blank: Cell, // value of the cells added when growing
+}
+
+impl VecTape {
+    pub fn new(config: &InterpreterConfig) -> Self {
+        VecTape {
+            cells: vec![initial_cell(config); config.initial_tape_size.max(1)],
+            start: 0,
+            origin: 0,
+            cursor: 0,
+            limit: config.max_tape_size,
+            grow_left: config.boundary == BoundaryPolicy::Grow,
+            blank: initial_cell(config),
+        }
+    }
+}
+
+impl Tape for VecTape {
+    fn get(&self) -> Cell {
+        self.cells[self.cursor]
+    }
+
+    fn set(&mut self, value: Cell) {
+        self.cells[self.cursor] = value;
+    }
+
+    fn move_left(&mut self, n: usize) -> Result<(), TapeError> {
+        let room = self.cursor - self.start;
+        let missing = n.saturating_sub(room);
+        if missing > 0 {
+            if !self.grow_left {
+                return Err(TapeError::Underflow { moved: room });
+            }
+            check_limit(self.limit, self.cells.len() - self.start, missing, room)?;
+            if missing > self.start {
+                // grow by at least the current size so that walking left stays linear
+                let extra = (missing - self.start).max(self.cells.len());
+                self.cells
+                    .splice(0..0, core::iter::repeat_n(self.blank, extra));
+                self.start += extra;
+                self.origin += extra;
+                self.cursor += extra;
+            }
+            self.start -= missing;
+        }
+        self.cursor -= n;
+        Ok(())
+    }
+
+    fn move_right(&mut self, n: usize) -> Result<(), TapeError> {
+        let cursor = self.cursor + n;
+        if cursor >= self.cells.len() {
+            let room = self.cells.len() - 1 - self.cursor;
+            let grow = cursor + 1 - self.cells.len();
+            check_limit(self.limit, self.cells.len() - self.start, grow, room)?;
+            self.cells.resize(cursor + 1, self.blank);
+        }
+        self.cursor = cursor;
+        Ok(())
+    }
+
+    fn cursor(&self) -> isize {
+        self.cursor as isize - self.origin as isize
+    }
+
+    fn cells(&self) -> Cow<'_, [Cell]> {
+        Cow::Borrowed(&self.cells[self.start..])
+    }
+
+    fn first_cell(&self) -> isize {
+        self.start as isize - self.origin as isize
+    }
+
+    // Single steps search the cells in place, past either end every cell is
+    // blank, so the first one new there ends the seek unless blank isn't 0.
+    fn seek_zero(&mut self, step: isize, max_moves: usize) -> Result<usize, TapeError> {
+        // the cells a seek of max_moves could look at, the pointer's own included
+        let reach = max_moves.saturating_add(1);
+        let moves = match step {
+            1 => {
+                let ahead = &self.cells[self.cursor..];
+                let window = ahead.len().min(reach);
+                match ahead[..window].iter().position(|&c| c == 0) {
+                    Some(i) => i,
+                    None if window == reach => max_moves,
+                    None => {
+                        self.cursor = self.cells.len() - 1;
+                        self.move_right(1)?;
+                        return Ok(window);
+                    }
+                }
+            }
+            -1 => {
+                let behind = &self.cells[self.start..=self.cursor];
+                let window = behind.len().min(reach);
+                match behind[behind.len() - window..]
+                    .iter()
+                    .rev()
+                    .position(|&c| c == 0)
+                {
+                    Some(i) => i,
+                    None if window == reach => max_moves,
+                    None => {
+                        self.cursor = self.start;
+                        self.move_left(1)?;
+                        return Ok(window);
+                    }
+                }
+            }
+            _ => return step_to_zero(self, step, max_moves),
+        };
+        if step < 0 {
+            self.cursor -= moves;
+        } else {
+            self.cursor += moves;
+        }
+        Ok(moves)
+    }
+}
+
+// Growing a tape of len cells by grow more, after room moves that need no growth.
+fn check_limit(
+    limit: Option<usize>,
+    len: usize,
+    grow: usize,
+    room: usize,
+) -> Result<(), TapeError> {
+    match limit {
+        Some(limit) if len + grow > limit => Err(TapeError::Overflow {
+            limit,
+            moved: room + limit.saturating_sub(len),
+        }),
+        _ => Ok(()),
+    }
+}
+
+// Fixed at initial_tape_size cells, moving off either end comes back in at the other.
+#[derive(Clone, Debug)]
+pub struct WrappingTape {
+    cells: Vec<Cell>,
+    cursor: usize,
+}
+
+impl WrappingTape {
+    pub fn new(config: &InterpreterConfig) -> Self {
+        WrappingTape {
+            cells: vec![initial_cell(config); config.initial_tape_size.max(1)],
+            cursor: 0,
+        }
+    }
+}
+
+impl Tape for WrappingTape {
+    fn get(&self) -> Cell {
+        self.cells[self.cursor]
+    }
+
+    fn set(&mut self, value: Cell) {
+        self.cells[self.cursor] = value;
+    }
+
+    fn move_left(&mut self, n: usize) -> Result<(), TapeError> {
+        let len = self.cells.len();
+        self.cursor = (self.cursor + len - n % len) % len;
+        Ok(())
+    }
+
+    fn move_right(&mut self, n: usize) -> Result<(), TapeError> {
+        self.cursor = (self.cursor + n % self.cells.len()) % self.cells.len();
+        Ok(())
+    }
+
+    fn cursor(&self) -> isize {
+        self.cursor as isize
+    }
+
+    fn cells(&self) -> Cow<'_, [Cell]> {
+        Cow::Borrowed(&self.cells)
+    }
+
+    // Gives up after going once around the tape.
+    fn seek_zero(&mut self, step: isize, max_moves: usize) -> Result<usize, TapeError> {
+        let mut moves = 0;
+        while self.get() != 0 && moves < self.cells.len().min(max_moves) {
+            self.move_right(step.rem_euclid(self.cells.len() as isize) as usize)?;
+            moves += 1;
+        }
+        Ok(moves)
+    }
+}
+
+// Fixed at initial_tape_size cells, moves past either end stop at the last cell
+// there, without an error.
+#[derive(Clone, Debug)]
+pub struct ClampedTape {
+    cells: Vec<Cell>,
+    cursor: usize,
+}
+
+impl ClampedTape {
+    pub fn new(config: &InterpreterConfig) -> Self {
+        ClampedTape {
+            cells: vec![initial_cell(config); config.initial_tape_size.max(1)],
+            cursor: 0,
+        }
+    }
+}
+
+impl Tape for ClampedTape {
+    fn get(&self) -> Cell {
+        self.cells[self.cursor]
+    }
+
+    fn set(&mut self, value: Cell) {
+        self.cells[self.cursor] = value;
+    }
+
+    fn move_left(&mut self, n: usize) -> Result<(), TapeError> {
+        self.cursor = self.cursor.saturating_sub(n);
+        Ok(())
+    }
+
+    fn move_right(&mut self, n: usize) -> Result<(), TapeError> {
+        self.cursor = self.cursor.saturating_add(n).min(self.cells.len() - 1);
+        Ok(())
+    }
+
+    fn cursor(&self) -> isize {
+        self.cursor as isize
+    }
+
+    fn cells(&self) -> Cow<'_, [Cell]> {
+        Cow::Borrowed(&self.cells)
+    }
+
+    // Gives up once the pointer is stuck at an end.
+    fn seek_zero(&mut self, step: isize, max_moves: usize) -> Result<usize, TapeError> {
+        let mut moves = 0;
+        while self.get() != 0 && moves < max_moves {
+            let cursor = self.cursor;
+            if step < 0 {
+                self.move_left(step.unsigned_abs())?;
+            } else {
+                self.move_right(step as usize)?;
+            }
+            moves += 1;
+            if self.cursor == cursor {
+                break;
+            }
+        }
+        Ok(moves)
+    }
+}
+
+// Only cells that hold a value other than the initial one take up memory, so
+// programs can visit cells millions of positions apart.
+#[derive(Clone, Debug)]
+pub struct SparseTape {
+    cells: BTreeMap<isize, Cell>,
+    cursor: isize,
+    low: isize,  // lowest position the pointer has been at
+    high: isize, // highest position the pointer has been at
+    limit: Option<usize>,
+    grow_left: bool,
+    blank: Cell, // value of every cell missing from cells
+}
+
+impl SparseTape {
+    pub fn new(config: &InterpreterConfig) -> Self {
+        SparseTape {
+            cells: BTreeMap::new(),
+            cursor: 0,
+            low: 0,
+            high: config.initial_tape_size.max(1) as isize - 1,
+            limit: config.max_tape_size,
+            grow_left: config.boundary == BoundaryPolicy::Grow,
+            blank: initial_cell(config),
+        }
+    }
+}
+
+impl Tape for SparseTape {
+    fn get(&self) -> Cell {
+        self.cells.get(&self.cursor).copied().unwrap_or(self.blank)
+    }
+
+    fn set(&mut self, value: Cell) {
+        if value == self.blank {
+            self.cells.remove(&self.cursor);
+        } else {
+            self.cells.insert(self.cursor, value);
+        }
+    }
+
+    fn move_left(&mut self, n: usize) -> Result<(), TapeError> {
+        let cursor = self.cursor - n as isize;
+        if cursor < self.low {
+            let room = (self.cursor - self.low) as usize;
+            if !self.grow_left && cursor < 0 {
+                return Err(TapeError::Underflow { moved: room });
+            }
+            let len = (self.high - self.low) as usize + 1;
+            check_limit(self.limit, len, (self.low - cursor) as usize, room)?;
+            self.low = cursor;
+        }
+        self.cursor = cursor;
+        Ok(())
+    }
+
+    fn move_right(&mut self, n: usize) -> Result<(), TapeError> {
+        let cursor = self.cursor + n as isize;
+        if cursor > self.high {
+            let room = (self.high - self.cursor) as usize;
+            let len = (self.high - self.low) as usize + 1;
+            check_limit(self.limit, len, (cursor - self.high) as usize, room)?;
+            self.high = cursor;
+        }
+        self.cursor = cursor;
+        Ok(())
+    }
+
+    fn cursor(&self) -> isize {
+        self.cursor
+    }
+
+    // as long as the stretch the pointer has been over, however little of it is stored
+    fn cells(&self) -> Cow<'_, [Cell]> {
+        let mut cells = vec![self.blank; (self.high - self.low) as usize + 1];
+        for (&position, &value) in &self.cells {
+            cells[(position - self.low) as usize] = value;
+        }
+        Cow::Owned(cells)
+    }
+
+    fn first_cell(&self) -> isize {
+        self.low
+    }
+
+    fn cell_count(&self) -> usize {
+        (self.high - self.low) as usize + 1
+    }
+
+    fn touched(&self) -> Vec<(isize, Cell)> {
+        self.cells
+            .iter()
+            .map(|(&position, &value)| (position, value))
+            .collect()
+    }
+
+    // Past the stored cells every cell is blank, so the seek gives up at the
+    // first one there.
+    fn seek_zero(&mut self, step: isize, max_moves: usize) -> Result<usize, TapeError> {
+        let mut moves = 0;
+        while self.get() != 0 && moves < max_moves {
+            if step < 0 {
+                self.move_left(step.unsigned_abs())?;
+            } else {
+                self.move_right(step as usize)?;
+            }
+            moves += 1;
+            let stored = if step < 0 {
+                self.cells
+                    .first_key_value()
+                    .is_some_and(|(&low, _)| self.cursor >= low)
+            } else {
+                self.cells
+                    .last_key_value()
+                    .is_some_and(|(&high, _)| self.cursor <= high)
+            };
+            if !stored {
+                break;
+            }
+        }
+        Ok(moves)
+    }
+}
+
+#[test]
+pub fn test_sparse_tape_far_apart_cells() -> Result<(), TapeError> {
+    let config = InterpreterConfig {
+        tape: TapeKind::Sparse,
+        ..InterpreterConfig::default()
+    };
+    let mut tape = SparseTape::new(&config);
+
+    tape.set(1);
+    tape.move_right(1 << 40)?;
+    tape.set(2);
+    tape.move_right(1 << 40)?;
+    tape.set(3);
+    tape.move_left(1 << 40)?;
+    assert_eq!(tape.get(), 2);
+    tape.move_left(1 << 40)?;
+    assert_eq!(tape.get(), 1);
+    assert_eq!(tape.cells.len(), 3);
+    assert_eq!(tape.touched(), [(0, 1), (1 << 40, 2), (2 << 40, 3)]);
+    assert_eq!(tape.move_left(1), Err(TapeError::Underflow { moved: 0 }));
+
+    Ok(())
+}
+
+#[test]
+pub fn test_tapes_agree_on_hello_world() -> Result<(), crate::HandError> {
+    let instructions = crate::parse_program(include_str!("../../test2.hand"))?;
+    let config = InterpreterConfig {
+        initial_tape_size: 16,
+        ..InterpreterConfig::default()
+    };
+    let run = |tape: &mut dyn Tape| -> Result<Vec<u8>, crate::HandError> {
+        let mut output = Vec::new();
+        crate::run_hand_ast_with_tape(&config, tape, std::io::empty(), &mut output, &instructions)?;
+        Ok(output)
+    };
+
+    let mut contiguous = VecTape::new(&config);
+    let mut wrapping = WrappingTape::new(&config);
+    let mut sparse = SparseTape::new(&config);
+
+    assert_eq!(run(&mut contiguous)?, b"Hello World!\n");
+    assert_eq!(run(&mut wrapping)?, b"Hello World!\n");
+    assert_eq!(run(&mut sparse)?, b"Hello World!\n");
+    assert_eq!(contiguous.cells, wrapping.cells);
+    assert_eq!(sparse.cells(), contiguous.cells());
+    assert_eq!(contiguous.cursor(), sparse.cursor());
+
+    Ok(())
+}
+
+#[test]
+pub fn test_grow_left() -> Result<(), TapeError> {
+    let config = InterpreterConfig {
+        boundary: BoundaryPolicy::Grow,
+        ..InterpreterConfig::default()
+    };
+    let tapes: [Box<dyn Tape>; 2] = [
+        Box::new(VecTape::new(&config)),
+        Box::new(SparseTape::new(&config)),
+    ];
+
+    for mut tape in tapes {
+        tape.set(7);
+        tape.move_left(3)?;
+        tape.set(tape.get() + 1);
+        assert_eq!(tape.cursor(), -3);
+        assert_eq!(tape.first_cell(), -3);
+        assert_eq!(*tape.cells(), [1, 0, 0, 7]);
+        tape.move_right(3)?;
+        assert_eq!(tape.get(), 7);
+        tape.move_left(3)?;
+        assert_eq!(tape.get(), 1);
+    }
+
+    let limited = InterpreterConfig {
+        max_tape_size: Some(3),
+        ..config
+    };
+    let mut tape = VecTape::new(&limited);
+    tape.move_left(1)?;
+    assert_eq!(
+        tape.move_left(3),
+        Err(TapeError::Overflow { limit: 3, moved: 1 })
+    );
+    assert_eq!(tape.cursor(), -1);
+
+    Ok(())
+}
+
+#[test]
+pub fn test_seek_zero() -> Result<(), TapeError> {
+    let config = InterpreterConfig {
+        boundary: BoundaryPolicy::Grow,
+        ..InterpreterConfig::default()
+    };
+    let mut contiguous = VecTape::new(&config);
+    let mut sparse = SparseTape::new(&config);
+    let tapes: [&mut dyn Tape; 2] = [&mut contiguous, &mut sparse];
+
+    for tape in tapes {
+        for value in [1, 2, 3, 0, 4] {
+            tape.set(value);
+            tape.move_right(1)?;
+        }
+        tape.move_left(5)?;
+        assert_eq!(tape.seek_zero(1, 2)?, 2);
+        assert_eq!(tape.cursor(), 2);
+        assert_eq!(tape.seek_zero(1, usize::MAX)?, 1);
+        assert_eq!(tape.cursor(), 3);
+        tape.move_right(1)?;
+        assert_eq!(tape.seek_zero(1, usize::MAX)?, 1);
+        assert_eq!(tape.cursor(), 5);
+        tape.move_left(1)?;
+        assert_eq!(tape.seek_zero(-2, usize::MAX)?, 3);
+        assert_eq!(tape.cursor(), -2);
+        tape.move_right(3)?;
+        assert_eq!(tape.seek_zero(-1, 1)?, 1);
+        assert_eq!(tape.seek_zero(-1, usize::MAX)?, 1);
+        assert_eq!(tape.cursor(), -1);
+        assert_eq!(tape.seek_zero(-1, usize::MAX)?, 0);
+    }
+
+    let mut tape = VecTape::new(&InterpreterConfig::default());
+    tape.set(1);
+    assert_eq!(
+        tape.seek_zero(-1, usize::MAX),
+        Err(TapeError::Underflow { moved: 0 })
+    );
+
+    // with nothing but blank cells that aren't 0 ahead, each seek goes one step
+    // past the cells there were
+    let config = InterpreterConfig {
+        initial_cell_value: 1,
+        ..InterpreterConfig::default()
+    };
+    let mut contiguous = VecTape::new(&config);
+    let mut sparse = SparseTape::new(&config);
+    sparse.set(0);
+    sparse.move_right(1)?;
+    sparse.set(2);
+    let tapes: [&mut dyn Tape; 2] = [&mut contiguous, &mut sparse];
+    for tape in tapes {
+        let cursor = tape.cursor();
+        tape.seek_zero(2, usize::MAX)?;
+        assert_eq!(tape.get(), 1);
+        assert_eq!(tape.cursor(), cursor + 2);
+        tape.seek_zero(1, usize::MAX)?;
+        assert_eq!(tape.cursor(), cursor + 3);
+    }
+
+    Ok(())
+}